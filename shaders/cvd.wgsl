@@ -0,0 +1,42 @@
+// color vision deficiency simulation, see sefenbu::cvd
+
+// Machado, Oliveira and Fernandes (2009) at severity 1.0, each vec3 is a row
+const PROTANOPIA: mat3x3<f32> = mat3x3<f32>(
+    vec3<f32>(0.152286, 1.052583, -0.204868),
+    vec3<f32>(0.114503, 0.786281, 0.099216),
+    vec3<f32>(-0.003882, -0.048116, 1.051998),
+);
+const DEUTERANOPIA: mat3x3<f32> = mat3x3<f32>(
+    vec3<f32>(0.367322, 0.860646, -0.227968),
+    vec3<f32>(0.280085, 0.672501, 0.047413),
+    vec3<f32>(-0.011820, 0.042940, 0.968881),
+);
+const TRITANOPIA: mat3x3<f32> = mat3x3<f32>(
+    vec3<f32>(1.255528, -0.076749, -0.178779),
+    vec3<f32>(-0.078411, 0.930809, 0.147602),
+    vec3<f32>(0.004733, 0.691367, 0.303900),
+);
+const LUMINANCE: vec3<f32> = vec3<f32>(0.2126, 0.7152, 0.0722);
+
+// linear sRGB as seen with the deficiency, 0 is normal vision, then protanopia, deuteranopia,
+// tritanopia and achromatopsia
+fn simulate_cvd(rgb: vec3<f32>, cvd: u32) -> vec3<f32> {
+    // a row vector times the matrix dots it with each column, so with each row as written above
+    switch cvd {
+        case 1u: {
+            return clamp(rgb * PROTANOPIA, vec3(0.), vec3(1.));
+        }
+        case 2u: {
+            return clamp(rgb * DEUTERANOPIA, vec3(0.), vec3(1.));
+        }
+        case 3u: {
+            return clamp(rgb * TRITANOPIA, vec3(0.), vec3(1.));
+        }
+        case 4u: {
+            return vec3(clamp(dot(rgb, LUMINANCE), 0., 1.));
+        }
+        default: {
+            return rgb;
+        }
+    }
+}
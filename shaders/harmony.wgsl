@@ -0,0 +1,13 @@
+// hues of a color harmony, see sefenbu::harmony
+
+// whether the hue, in degrees, is within `width` of one of the harmony hues. Unused hues are
+// negative, the wheel wraps around at 360
+fn in_harmony(hue: f32, harmony: vec4<f32>, width: f32) -> bool {
+    for (var i = 0; i < 4; i++) {
+        let d = abs(hue - harmony[i]);
+        if harmony[i] >= 0. && min(d, 360. - d) <= width {
+            return true;
+        }
+    }
+    return false;
+}
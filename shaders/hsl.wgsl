@@ -1,5 +1,6 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
-#import "shaders/hsl_common.wgsl"::{
+#import "shaders://cvd.wgsl"::simulate_cvd
+#import "shaders://hsl_common.wgsl"::{
     HSL,
     srgb_to_hsl
 }
@@ -8,19 +9,32 @@
 @group(2) @binding(1) var<uniform> delta: f32;
 @group(2) @binding(2) var img_texture: texture_2d<f32>;
 @group(2) @binding(3) var img_sampler: sampler;
+@group(2) @binding(5) var mask_texture: texture_2d<f32>;
+@group(2) @binding(6) var mask_sampler: sampler;
+@group(2) @binding(7) var<uniform> invert_mask: f32;
+@group(2) @binding(8) var<uniform> cvd: u32;
+@group(2) @binding(9) var<uniform> cvd_analysis: f32;
 
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
     var pt: vec4<f32> = textureSample(img_texture, img_sampler, mesh.uv);
-    let hsl: HSL = srgb_to_hsl(pt.r, pt.g, pt.b);
+    // shown as seen with the simulated deficiency, and sliced that way too if it is analyzed
+    let seen = simulate_cvd(pt.rgb, cvd);
+    let c = select(pt.rgb, seen, cvd_analysis > 0.);
+    pt = vec4(seen, pt.a);
+    let hsl: HSL = srgb_to_hsl(c.r, c.g, c.b);
 
-    // Make opaque and grayscale if not in color slice
-    if abs(hsl.l * 100. - h) > (delta / 2.) {
+    // pixels left out by the mask are in no slice
+    let m: vec4<f32> = textureSample(mask_texture, mask_sampler, mesh.uv);
+    let masked = abs(invert_mask - (m.r + m.g + m.b) / 3. * m.a) <= 0.;
+
+    // Make translucent and grayscale if not in color slice
+    if masked || abs(hsl.l * 100. - h) > (delta / 2.) {
         let avg = (pt.r + pt.g + pt.b) / 3;
         pt.r = avg;
         pt.g = avg;
         pt.b = avg;
-        pt.a = 0.15;
+        pt.a *= 0.15;
     }
 
     return pt;
@@ -1,5 +1,5 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
-#import "shaders/hsl_common.wgsl"::{
+#import "shaders://hsl_common.wgsl"::{
     HSL,
     hsl_to_srgb
 }
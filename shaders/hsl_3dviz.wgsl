@@ -1,5 +1,5 @@
 #import bevy_pbr::forward_io::VertexOutput
-#import "shaders/hsl_common.wgsl"::{
+#import "shaders://hsl_common.wgsl"::{
     HSL,
     hsl_to_srgb
 }
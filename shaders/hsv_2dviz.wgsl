@@ -1,11 +1,13 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
-#import "shaders/hsv_common.wgsl"::{
+#import "shaders://harmony.wgsl"::in_harmony
+#import "shaders://hsv_common.wgsl"::{
     HSV,
     hsv_to_srgb
 }
 
 @group(2) @binding(0) var<uniform> h: f32;
 @group(2) @binding(1) var<uniform> delta: f32;
+@group(2) @binding(2) var<uniform> harmony: vec4<f32>;
 
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
@@ -15,6 +17,8 @@ fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
     var a: f32 = 0.25;
     if abs(pos.x * 360. - h) < (delta / 2.) {
         a = 1.;
+    } else if in_harmony(pos.x * 360., harmony, delta / 2.) {
+        a = 0.6;
     }
 
     return vec4<f32>(rgb.r, rgb.g, rgb.b, a);
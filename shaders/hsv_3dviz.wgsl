@@ -1,5 +1,5 @@
 #import bevy_pbr::forward_io::VertexOutput
-#import "shaders/hsv_common.wgsl"::{
+#import "shaders://hsv_common.wgsl"::{
     HSV,
     hsv_to_srgb
 }
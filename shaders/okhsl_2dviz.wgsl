@@ -1,5 +1,5 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
-#import "shaders/oklab_common.wgsl"::{
+#import "shaders://oklab_common.wgsl"::{
     HSL,
     RGB,
     okhsl_to_srgb
@@ -1,5 +1,5 @@
 #import bevy_pbr::forward_io::VertexOutput
-#import "shaders/oklab_common.wgsl"::{
+#import "shaders://oklab_common.wgsl"::{
     HSL,
     RGB,
     okhsl_to_srgb
@@ -1,5 +1,7 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
-#import "shaders/oklab_common.wgsl"::{
+#import "shaders://cvd.wgsl"::simulate_cvd
+#import "shaders://harmony.wgsl"::in_harmony
+#import "shaders://oklab_common.wgsl"::{
     HSV,
     RGB,
     srgb_to_okhsv,
@@ -10,19 +12,40 @@
 @group(2) @binding(1) var<uniform> delta: f32;
 @group(2) @binding(2) var img_texture: texture_2d<f32>;
 @group(2) @binding(3) var img_sampler: sampler;
+@group(2) @binding(4) var<uniform> achromatic: f32;
+@group(2) @binding(5) var mask_texture: texture_2d<f32>;
+@group(2) @binding(6) var mask_sampler: sampler;
+@group(2) @binding(7) var<uniform> invert_mask: f32;
+@group(2) @binding(8) var<uniform> cvd: u32;
+@group(2) @binding(9) var<uniform> cvd_analysis: f32;
+@group(2) @binding(10) var<uniform> harmony: vec4<f32>;
 
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
     var pt: vec4<f32> = textureSample(img_texture, img_sampler, mesh.uv);
-    let okhsv: HSV = srgb_to_okhsv(to_non_linear_rgb(pt.r, pt.g, pt.b));
+    // shown as seen with the simulated deficiency, and sliced that way too if it is analyzed
+    let seen = simulate_cvd(pt.rgb, cvd);
+    let c = select(pt.rgb, seen, cvd_analysis > 0.);
+    pt = vec4(seen, pt.a);
+    let okhsv: HSV = srgb_to_okhsv(to_non_linear_rgb(c.r, c.g, c.b));
 
-    // Make opaque and grayscale if not in color slice
-    if abs(okhsv.h * 360. - h) > (delta / 2.) {
+    // pixels left out by the mask are in no slice
+    let m: vec4<f32> = textureSample(mask_texture, mask_sampler, mesh.uv);
+    let masked = abs(invert_mask - (m.r + m.g + m.b) / 3. * m.a) <= 0.;
+
+    // Make translucent and grayscale if not in color slice, achromatic pixels are in none. The
+    // hues in harmony with it are kept in color, but fainter
+    let off = masked || okhsv.s * okhsv.v < achromatic;
+    if off || abs(okhsv.h * 360. - h) > (delta / 2.) {
+        if !off && in_harmony(okhsv.h * 360., harmony, delta / 2.) {
+            pt.a *= 0.5;
+            return pt;
+        }
         let avg = (pt.r + pt.g + pt.b) / 3;
         pt.r = avg;
         pt.g = avg;
         pt.b = avg;
-        pt.a = 0.15;
+        pt.a *= 0.15;
     }
 
     return pt;
@@ -1,5 +1,5 @@
 #import bevy_pbr::forward_io::VertexOutput
-#import "shaders/oklab_common.wgsl"::{
+#import "shaders://oklab_common.wgsl"::{
     HSV,
     RGB,
     okhsv_to_srgb
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, input::mouse::AccumulatedMouseScroll, prelude::*};
+use serde::{Deserialize, de::IntoDeserializer};
+
+/// Every action once, as `Variant => config_name`. The Action enum, its names, the Bindings
+/// fields and Bindings::get all follow from this one list
+macro_rules! actions {
+    ($($(#[$doc:meta])* $variant:ident => $field:ident,)*) => {
+        /// Things the user can do, independent of which key or button triggers them
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum Action {
+            $($(#[$doc])* $variant,)*
+        }
+
+        impl Action {
+            pub const ALL: [Action; [$(Action::$variant),*].len()] = [$(Action::$variant),*];
+
+            /// as written in the config, e.g. fine_step
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Action::$variant => stringify!($field),)*
+                }
+            }
+        }
+
+        /// Action to input mapping, every field is optional in the config and falls back to the
+        /// default
+        #[derive(Resource, Deserialize, Debug)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct Bindings {
+            $(pub $field: Vec<Binding>,)*
+        }
+
+        impl Bindings {
+            pub fn get(&self, action: Action) -> &[Binding] {
+                match action {
+                    $(Action::$variant => &self.$field,)*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    /// increase the sliced param
+    Increment => increment,
+    /// decrease the sliced param
+    Decrement => decrement,
+    /// held: step one slice per press instead of repeating
+    FineStep => fine_step,
+    /// held: step ten slices at a time
+    CoarseStep => coarse_step,
+    /// held: drag to orbit the 3d viz
+    Orbit => orbit,
+    /// jump to the slice under the cursor in the 2d viz
+    Pick => pick,
+    /// all at once: back to the first slice, the 3d viz to its initial orbit and the image fit to
+    /// its panel
+    Reset => reset,
+    /// switch between windowed and borderless fullscreen
+    ToggleFullscreen => toggle_fullscreen,
+    /// held: drag the splitters between panels
+    Resize => resize,
+    /// maximize the panel under the cursor, or restore the layout
+    Maximize => maximize,
+    /// move the image to the other side
+    SwapPanels => swap_panels,
+    /// zoom in on the panel under the cursor
+    ZoomIn => zoom_in,
+    /// zoom out of the panel under the cursor
+    ZoomOut => zoom_out,
+    /// held: drag to pan the image
+    Pan => pan,
+    /// show the image at one image pixel per screen pixel
+    ActualSize => actual_size,
+    /// fit the whole image in its panel
+    FitView => fit_view,
+    /// look at the 3d viz from above
+    ViewTop => view_top,
+    /// look at the 3d viz from the front
+    ViewFront => view_front,
+    /// look at the 3d viz from the side
+    ViewSide => view_side,
+    /// switch the 3d viz between perspective and orthographic
+    ToggleProjection => toggle_projection,
+    /// next histogram height scaling
+    CycleScale => cycle_scale,
+    /// next reference for the 3d viz heights
+    CycleNormalization => cycle_normalization,
+    /// next shape to select a region of the image with, or back to panning
+    CycleSelectTool => cycle_select_tool,
+    /// held: draw the selection on the image
+    Select => select,
+    /// finish a polygon selection
+    CloseSelection => close_selection,
+    /// analyze the whole image again
+    ClearSelection => clear_selection,
+    /// highlight the pixels that were outside of sRGB before conversion
+    ToggleGamut => toggle_gamut,
+    /// brighten 16-bit and floating-point images by half a stop
+    ExposureUp => exposure_up,
+    /// darken 16-bit and floating-point images by half a stop
+    ExposureDown => exposure_down,
+    /// next way to bring 16-bit and floating-point values above 1.0 into range
+    CycleToneMap => cycle_tonemap,
+    /// show the next frame of an animation or sequence
+    NextFrame => next_frame,
+    /// show the previous frame of an animation or sequence
+    PrevFrame => prev_frame,
+    /// analyze the image on the clipboard instead
+    Paste => paste,
+    /// simulate the next color vision deficiency, or none after the last
+    CycleCvd => cycle_cvd,
+    /// also analyze the colors as seen with the simulated deficiency
+    ToggleCvdAnalysis => toggle_cvd_analysis,
+    /// add the color under the cursor to the contrast panel
+    PickSwatch => pick_swatch,
+    /// empty the contrast panel
+    ClearSwatches => clear_swatches,
+    /// mark the next harmony on the hue axis, or none after the last
+    CycleHarmony => cycle_harmony,
+    /// build the harmony on the dominant hue of the image instead of the current one
+    ToggleHarmonyBase => toggle_harmony_base,
+    /// show the terminal color scheme made from the image
+    ToggleScheme => toggle_scheme,
+    /// put the terminal color scheme on the clipboard, as Xresources
+    CopyScheme => copy_scheme,
+    /// show the image quantized to the palette
+    ToggleQuantize => toggle_quantize,
+    /// show how far each pixel is from its nearest palette color instead
+    ToggleErrorMap => toggle_error_map,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WheelDirection {
+    Up,
+    Down,
+}
+
+/// A physical input, written as e.g. "KeyK", "ArrowUp", "MouseLeft" or "WheelDown" in the config
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Wheel(WheelDirection),
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        // reuse bevy's serde names for keys and buttons
+        fn parse<'de, T: Deserialize<'de>>(s: &'de str) -> Option<T> {
+            T::deserialize(IntoDeserializer::<serde::de::value::Error>::into_deserializer(s)).ok()
+        }
+
+        match s.as_str() {
+            "WheelUp" => Ok(Binding::Wheel(WheelDirection::Up)),
+            "WheelDown" => Ok(Binding::Wheel(WheelDirection::Down)),
+            _ => {
+                let parsed = match s.strip_prefix("Mouse") {
+                    Some(button) => parse(button).map(Binding::Mouse),
+                    None => parse(&s).map(Binding::Key),
+                };
+                parsed.ok_or(format!("unknown key or button '{}'", s))
+            }
+        }
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::*;
+        Bindings {
+            increment: vec![
+                Key(KeyCode::KeyK),
+                Key(KeyCode::ArrowUp),
+                Key(KeyCode::ArrowRight),
+                Wheel(WheelDirection::Up),
+            ],
+            decrement: vec![
+                Key(KeyCode::KeyJ),
+                Key(KeyCode::ArrowDown),
+                Key(KeyCode::ArrowLeft),
+                Wheel(WheelDirection::Down),
+            ],
+            fine_step: vec![Key(KeyCode::ControlLeft), Key(KeyCode::ControlRight)],
+            coarse_step: vec![Key(KeyCode::ShiftLeft), Key(KeyCode::ShiftRight)],
+            orbit: vec![Mouse(MouseButton::Left)],
+            pick: vec![Mouse(MouseButton::Left)],
+            reset: vec![Key(KeyCode::KeyR)],
+            toggle_fullscreen: vec![Key(KeyCode::F11)],
+            resize: vec![Mouse(MouseButton::Left)],
+            maximize: vec![Key(KeyCode::KeyM)],
+            swap_panels: vec![Key(KeyCode::KeyS)],
+            zoom_in: vec![Key(KeyCode::Equal), Wheel(WheelDirection::Up)],
+            zoom_out: vec![Key(KeyCode::Minus), Wheel(WheelDirection::Down)],
+            pan: vec![Mouse(MouseButton::Left), Mouse(MouseButton::Middle)],
+            actual_size: vec![Key(KeyCode::Digit1)],
+            fit_view: vec![Key(KeyCode::Digit0)],
+            view_top: vec![Key(KeyCode::Numpad7), Key(KeyCode::KeyT)],
+            view_front: vec![Key(KeyCode::Numpad1), Key(KeyCode::KeyF)],
+            view_side: vec![Key(KeyCode::Numpad3), Key(KeyCode::KeyG)],
+            toggle_projection: vec![Key(KeyCode::Numpad5), Key(KeyCode::KeyO)],
+            cycle_scale: vec![Key(KeyCode::KeyL)],
+            cycle_normalization: vec![Key(KeyCode::KeyN)],
+            cycle_select_tool: vec![Key(KeyCode::KeyV)],
+            select: vec![Mouse(MouseButton::Left)],
+            close_selection: vec![Key(KeyCode::Enter), Key(KeyCode::NumpadEnter)],
+            clear_selection: vec![Key(KeyCode::Escape), Key(KeyCode::Delete)],
+            toggle_gamut: vec![Key(KeyCode::KeyW)],
+            exposure_up: vec![Key(KeyCode::BracketRight)],
+            exposure_down: vec![Key(KeyCode::BracketLeft)],
+            cycle_tonemap: vec![Key(KeyCode::KeyE)],
+            next_frame: vec![Key(KeyCode::Period)],
+            prev_frame: vec![Key(KeyCode::Comma)],
+            paste: vec![Key(KeyCode::KeyP)],
+            cycle_cvd: vec![Key(KeyCode::KeyC)],
+            toggle_cvd_analysis: vec![Key(KeyCode::KeyX)],
+            pick_swatch: vec![Key(KeyCode::KeyI)],
+            clear_swatches: vec![Key(KeyCode::Backspace)],
+            cycle_harmony: vec![Key(KeyCode::KeyH)],
+            toggle_harmony_base: vec![Key(KeyCode::KeyD)],
+            toggle_scheme: vec![Key(KeyCode::KeyA)],
+            copy_scheme: vec![Key(KeyCode::KeyY)],
+            toggle_quantize: vec![Key(KeyCode::KeyQ)],
+            toggle_error_map: vec![Key(KeyCode::KeyZ)],
+        }
+    }
+}
+
+impl Bindings {
+    /// A key bound to two actions would do both at once. The mouse buttons and the wheel are
+    /// shared on purpose, the panel under the cursor tells which action is meant
+    pub fn check(&self) -> Result<(), String> {
+        let mut seen: HashMap<KeyCode, Action> = HashMap::new();
+        for action in Action::ALL {
+            for binding in self.get(action) {
+                if let Binding::Key(key) = binding
+                    && let Some(other) = seen.insert(*key, action)
+                    && other != action
+                {
+                    return Err(format!(
+                        "{:?} is bound to both {} and {}",
+                        key,
+                        other.name(),
+                        action.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Query actions instead of raw input
+#[derive(SystemParam)]
+pub struct Actions<'w> {
+    bindings: Res<'w, Bindings>,
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    scroll: Res<'w, AccumulatedMouseScroll>,
+}
+
+impl Actions<'_> {
+    /// the wheel has no held state, so a scroll counts as both pressed and just pressed
+    fn scrolled(&self, dir: WheelDirection) -> bool {
+        match dir {
+            WheelDirection::Up => self.scroll.delta.y > 0.,
+            WheelDirection::Down => self.scroll.delta.y < 0.,
+        }
+    }
+
+    /// like pressed, but ignoring the wheel
+    pub fn button_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|b| match b {
+            Binding::Key(k) => self.keyboard.pressed(*k),
+            Binding::Mouse(m) => self.mouse.pressed(*m),
+            Binding::Wheel(_) => false,
+        })
+    }
+
+    /// like just_pressed, but ignoring the wheel
+    pub fn button_just_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|b| match b {
+            Binding::Key(k) => self.keyboard.just_pressed(*k),
+            Binding::Mouse(m) => self.mouse.just_pressed(*m),
+            Binding::Wheel(_) => false,
+        })
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|b| match b {
+            Binding::Key(k) => self.keyboard.pressed(*k),
+            Binding::Mouse(m) => self.mouse.pressed(*m),
+            Binding::Wheel(d) => self.scrolled(*d),
+        })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|b| match b {
+            Binding::Key(k) => self.keyboard.just_pressed(*k),
+            Binding::Mouse(m) => self.mouse.just_pressed(*m),
+            Binding::Wheel(d) => self.scrolled(*d),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_bind_each_key_once() {
+        assert_eq!(Bindings::default().check(), Ok(()));
+    }
+}
@@ -1,15 +1,201 @@
+use std::str::FromStr;
+
 use bevy::prelude::Resource;
-use clap::Parser;
+use clap::{Parser, builder::PossibleValuesParser, builder::TypedValueParser};
+use sefenbu::{Deficiency, Harmony, Metric, Space, ToneMap};
+
+use crate::{
+    formats,
+    scaling::{Normalization, ScaleMode},
+    weights::{AlphaHandling, Sampling},
+};
 
 #[derive(Parser, Resource)]
 #[command(version, about, long_about = None)]
 #[command(arg_required_else_help(true))]
+#[command(after_help = formats::supported_help())]
 pub struct Cli {
-    /// Input image
-    #[arg(value_name = "FILE")]
-    pub file: String,
+    /// Input image, animation, or directory of numbered frames, - to read an image from stdin
+    #[arg(value_name = "FILE", required_unless_present = "clipboard")]
+    pub file: Option<String>,
+
+    /// Analyze the image on the clipboard instead of a file
+    #[arg(long, conflicts_with = "file")]
+    pub clipboard: bool,
 
     /// Color space
+    #[arg(
+        short,
+        long,
+        default_value_t = Space::Okhsv,
+        value_parser = PossibleValuesParser::new(Space::ALL.map(Space::name))
+            .map(|s| s.parse::<Space>().unwrap()),
+    )]
+    pub using: Space,
+
+    /// Config file, defaults to $XDG_CONFIG_HOME/sefenbu/config.toml
+    #[arg(short, long)]
+    pub config: Option<String>,
+
+    /// Start in a window instead of fullscreen
     #[arg(short, long)]
-    pub using: Option<String>,
+    pub windowed: bool,
+
+    /// Window size, e.g. 1280x720, implies --windowed
+    #[arg(short, long, value_name = "WxH")]
+    pub size: Option<WindowSize>,
+
+    /// Index of the monitor to open on, defaults to the primary monitor
+    #[arg(short, long, value_name = "INDEX")]
+    pub monitor: Option<usize>,
+
+    /// Histogram height scaling
+    #[arg(long, value_enum, default_value_t = ScaleMode::Linear)]
+    pub scale: ScaleMode,
+
+    /// Percentile of non-empty bins shown at full height with --scale percentile
+    #[arg(long, default_value_t = 99., value_parser = parse_percentile)]
+    pub percentile: f32,
+
+    /// What the 3d viz heights are relative to
+    #[arg(long, value_enum, default_value_t = Normalization::Slice)]
+    pub normalize: Normalization,
+
+    /// Chroma in percent below which pixels have no hue and go in their own bucket, 0 to disable
+    #[arg(long, value_name = "PERCENT", default_value_t = 2., value_parser = parse_achromatic)]
+    pub achromatic: f32,
+
+    /// Keep achromatic pixels in the hue slices of the 3d viz and the image filter
+    #[arg(long)]
+    pub keep_achromatic: bool,
+
+    /// How transparent pixels count in the statistics
+    #[arg(long, value_enum, default_value_t = AlphaHandling::Exclude)]
+    pub alpha: AlphaHandling,
+
+    /// Grayscale image of the same size, white pixels are analyzed and black ones left out
+    #[arg(long, value_name = "FILE")]
+    pub mask: Option<String>,
+
+    /// Analyze the black parts of the mask instead
+    #[arg(long, requires = "mask")]
+    pub invert_mask: bool,
+
+    /// Analyze at most this many pixels, refined from a coarse sample
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_pixels: Option<u64>,
+
+    /// How pixels are picked for the coarse and --max-pixels samples
+    #[arg(long, value_enum, default_value_t = Sampling::Stratified)]
+    pub sampling: Sampling,
+
+    /// Seed for the random pixel picks
+    #[arg(long, value_name = "SEED", default_value_t = 0)]
+    pub sample_seed: u64,
+
+    /// Stops to brighten 16-bit and floating-point images by before they are analyzed
+    #[arg(
+        long,
+        value_name = "STOPS",
+        default_value_t = 0.,
+        allow_negative_numbers = true
+    )]
+    pub exposure: f32,
+
+    /// How 16-bit and floating-point values above 1.0 are brought into range
+    #[arg(
+        long,
+        default_value_t = ToneMap::Clip,
+        value_parser = PossibleValuesParser::new(ToneMap::ALL.map(ToneMap::name))
+            .map(|s| s.parse::<ToneMap>().unwrap()),
+    )]
+    pub tonemap: ToneMap,
+
+    /// Show the image as seen with a color vision deficiency
+    #[arg(
+        long,
+        value_name = "DEFICIENCY",
+        value_parser = PossibleValuesParser::new(Deficiency::ALL.map(Deficiency::name))
+            .map(|s| s.parse::<Deficiency>().unwrap()),
+    )]
+    pub cvd: Option<Deficiency>,
+
+    /// Also analyze the colors as seen with the simulated deficiency
+    #[arg(long)]
+    pub cvd_analysis: bool,
+
+    /// Mark the hues in harmony with the current one on the hue axis and in the image, when
+    /// slicing by hue
+    #[arg(
+        long,
+        value_name = "HARMONY",
+        value_parser = PossibleValuesParser::new(Harmony::ALL.map(Harmony::name))
+            .map(|s| s.parse::<Harmony>().unwrap()),
+    )]
+    pub harmony: Option<Harmony>,
+
+    /// Build the harmony on the dominant hue of the image instead of the current one
+    #[arg(long)]
+    pub harmony_dominant: bool,
+
+    /// Colors to quantize the image to, as #rrggbb anywhere in the file, e.g. one per line or
+    /// Xresources. Defaults to the picked colors
+    #[arg(long, value_name = "FILE")]
+    pub palette: Option<String>,
+
+    /// How the distance to the nearest palette color is measured
+    #[arg(
+        long,
+        value_name = "METRIC",
+        default_value_t = Metric::Oklab,
+        value_parser = PossibleValuesParser::new(Metric::ALL.map(Metric::name))
+            .map(|s| s.parse::<Metric>().unwrap()),
+    )]
+    pub delta_e: Metric,
+
+    /// Take the image as sRGB, ignoring the color space it declares
+    #[arg(long)]
+    pub ignore_profile: bool,
+
+    /// Load shaders from this directory instead of the built-in ones
+    #[arg(long, value_name = "DIR")]
+    pub shader_dir: Option<String>,
+}
+
+fn parse_percentile(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(p) if p > 0. && p <= 100. => Ok(p),
+        _ => Err(format!("expected a number in (0, 100], got '{}'", s)),
+    }
+}
+
+fn parse_achromatic(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(p) if (0. ..=100.).contains(&p) => Ok(p),
+        _ => Err(format!("expected a number in [0, 100], got '{}'", s)),
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct WindowSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FromStr for WindowSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("expected WIDTHxHEIGHT, got '{}'", s);
+        let (w, h) = s.split_once('x').ok_or_else(err)?;
+        let width: u32 = w.trim().parse().map_err(|_| err())?;
+        let height: u32 = h.trim().parse().map_err(|_| err())?;
+        if width == 0 || height == 0 {
+            return Err(err());
+        }
+        Ok(WindowSize {
+            width: width as f32,
+            height: height as f32,
+        })
+    }
 }
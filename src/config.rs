@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::bindings::Bindings;
+
+/// Contents of config.toml, all tables are optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bindings: Bindings,
+}
+
+/// $XDG_CONFIG_HOME/sefenbu/config.toml, falling back to ~/.config
+fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("sefenbu").join("config.toml"))
+}
+
+impl Config {
+    /// load the given config, or the default one if it exists
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let path = match path {
+            Some(p) => Some(PathBuf::from(p)),
+            None => default_path().filter(|p| p.exists()),
+        };
+        let invalid = |e: &dyn std::fmt::Display| match &path {
+            Some(p) => format!("Invalid config '{}': {}", p.to_string_lossy(), e),
+            None => format!("Invalid default config: {}", e),
+        };
+
+        let config: Config = match &path {
+            Some(p) => {
+                let content = std::fs::read_to_string(p)
+                    .map_err(|e| format!("Cannot read config '{}': {}", p.to_string_lossy(), e))?;
+                toml::from_str(&content).map_err(|e| invalid(&e))?
+            }
+            None => Config::default(),
+        };
+        // also the defaults, which fill in whatever the config leaves out
+        config.bindings.check().map_err(|e| invalid(&e))?;
+        Ok(config)
+    }
+}
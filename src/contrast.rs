@@ -0,0 +1,123 @@
+use palette::{FromColor, Okhsl, Srgb};
+
+/// WCAG 2.x AA ratio for body text
+pub const WCAG_AA: f32 = 4.5;
+/// APCA Lc for body text, either polarity
+pub const APCA_BODY: f32 = 60.;
+
+/// share of each channel in the luminance of a linear sRGB color
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+/// the APCA 0.0.98G-4g coefficients, which differ slightly from the ones above
+const APCA_LUMINANCE: [f32; 3] = [0.2126729, 0.7151522, 0.0721750];
+/// lightness steps tried by fix_lightness and reach_ratio
+const LIGHTNESS_STEP: f32 = 0.005;
+
+/// WCAG 2.x contrast ratio, from 1 to 21, the same either way round
+pub fn wcag_ratio(a: Srgb, b: Srgb) -> f32 {
+    let luminance = |c: Srgb| {
+        let lin = c.into_linear();
+        LUMINANCE[0] * lin.red + LUMINANCE[1] * lin.green + LUMINANCE[2] * lin.blue
+    };
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// APCA 0.0.98G-4g lightness contrast Lc of text on a background, about 106 for black on white
+/// and -108 for white on black
+pub fn apca(text: Srgb, background: Srgb) -> f32 {
+    // the screen luminance APCA assumes, with a soft clamp near black
+    let luminance = |c: Srgb| {
+        let y = APCA_LUMINANCE[0] * c.red.powf(2.4)
+            + APCA_LUMINANCE[1] * c.green.powf(2.4)
+            + APCA_LUMINANCE[2] * c.blue.powf(2.4);
+        if y > 0.022 {
+            y
+        } else {
+            y + (0.022 - y).powf(1.414)
+        }
+    };
+    let (text, background) = (luminance(text), luminance(background));
+    if (background - text).abs() < 0.0005 {
+        return 0.;
+    }
+    let lc = if background > text {
+        // dark text on a light background
+        let sapc = (background.powf(0.56) - text.powf(0.57)) * 1.14;
+        if sapc < 0.1 { 0. } else { sapc - 0.027 }
+    } else {
+        let sapc = (background.powf(0.65) - text.powf(0.62)) * 1.14;
+        if sapc > -0.1 { 0. } else { sapc + 0.027 }
+    };
+    lc * 100.
+}
+
+/// Whether the text passes both WCAG AA and APCA for body text on the background
+pub fn legible(text: Srgb, background: Srgb) -> bool {
+    wcag_ratio(text, background) >= WCAG_AA && apca(text, background).abs() >= APCA_BODY
+}
+
+/// The OKHSL lightness closest to that of the text, keeping its hue and saturation, that makes it
+/// legible on the background. None if no lightness does
+pub fn fix_lightness(text: Srgb, background: Srgb) -> Option<f32> {
+    nearest_lightness(text, |text| legible(text, background))
+}
+
+/// The OKHSL lightness closest to that of the text, keeping its hue and saturation, that gives it
+/// at least `ratio` WCAG contrast with the background. None if no lightness does
+pub fn reach_ratio(text: Srgb, background: Srgb, ratio: f32) -> Option<f32> {
+    nearest_lightness(text, |text| wcag_ratio(text, background) >= ratio)
+}
+
+/// the OKHSL lightness closest to that of the color that passes, in LIGHTNESS_STEP steps
+fn nearest_lightness(color: Srgb, passes: impl Fn(Srgb) -> bool) -> Option<f32> {
+    let okhsl = Okhsl::from_color(color);
+    let passes_at = |l: f32| {
+        let color = Srgb::from_color(Okhsl::new(okhsl.hue, okhsl.saturation, l));
+        passes(color).then_some(l)
+    };
+    let steps = (1. / LIGHTNESS_STEP) as i32;
+    (0..=steps).find_map(|k| {
+        let darker = okhsl.lightness - k as f32 * LIGHTNESS_STEP;
+        let lighter = okhsl.lightness + k as f32 * LIGHTNESS_STEP;
+        [darker, lighter]
+            .into_iter()
+            .filter(|l| (0. ..=1.).contains(l))
+            .find_map(passes_at)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Srgb = Srgb::new(0., 0., 0.);
+    const WHITE: Srgb = Srgb::new(1., 1., 1.);
+
+    #[test]
+    fn wcag_black_and_white() {
+        assert!((wcag_ratio(BLACK, WHITE) - 21.).abs() < 1e-3);
+        assert_eq!(wcag_ratio(BLACK, WHITE), wcag_ratio(WHITE, BLACK));
+        assert_eq!(wcag_ratio(WHITE, WHITE), 1.);
+    }
+
+    #[test]
+    fn apca_black_and_white() {
+        assert!((apca(BLACK, WHITE) - 106.04).abs() < 0.1);
+        assert!((apca(WHITE, BLACK) + 107.88).abs() < 0.1);
+        assert_eq!(apca(WHITE, WHITE), 0.);
+    }
+
+    #[test]
+    fn reach_ratio_on_white() {
+        let gray = Srgb::new(0.6, 0.6, 0.6);
+        let at = |l: f32| {
+            let okhsl = Okhsl::from_color(gray);
+            Srgb::from_color(Okhsl::new(okhsl.hue, okhsl.saturation, l))
+        };
+        // the lightest gray that passes, the next step no longer does
+        let l = reach_ratio(gray, WHITE, WCAG_AA).unwrap();
+        assert!(wcag_ratio(at(l), WHITE) >= WCAG_AA);
+        assert!(wcag_ratio(at(l + LIGHTNESS_STEP), WHITE) < WCAG_AA);
+        assert_eq!(reach_ratio(gray, WHITE, 22.), None);
+    }
+}
@@ -1,31 +1,155 @@
-use bevy::{input::mouse::AccumulatedMouseMotion, prelude::*};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::{
+    input::mouse::AccumulatedMouseMotion,
+    prelude::*,
+    render::camera::ScalingMode,
+    ui::RelativeCursorPosition,
+    window::{PrimaryWindow, WindowMode},
+};
+use palette::Srgb;
+use sefenbu::{Deficiency, Harmony};
 
 use crate::{
+    bindings::{Action, Actions},
+    decoded::{self, Decoded},
+    frames::Frames,
+    harmony_overlay::Harmonies,
+    hdr::{Exposure, HdrSource, EXPOSURE_STEP},
+    layout::Layout,
     providers::generic::CSpaceProvider,
-    scene::{ImageCanvas, ImageLoader},
-    Background, Viz2DCanvas, Viz3DMesh, COLOR_3D_VIZ_COORD, IMG_VIEW_W_RATIO, VIZ3D_H_RATIO,
+    quantize_view::Quantization,
+    scaling::Scaling,
+    scene::{viz3d_cam_transform, CamViewPort, GamutOverlay, ImageLoader, TimelineStrip},
+    selection::{Region, Roi, SelectTool, Selection},
+    simulation::Simulation,
+    swatches::Swatches,
+    terminal::TerminalScheme,
+    Background, COLOR_3D_VIZ_COORD,
 };
 
+/// Camera position around the 3d viz
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+impl Orbit {
+    fn from_transform(transform: &Transform) -> Self {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        Orbit {
+            yaw,
+            pitch,
+            distance: transform.translation.distance(COLOR_3D_VIZ_COORD),
+        }
+    }
+
+    fn transform(&self) -> Transform {
+        let rotation = Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.);
+        Transform::from_translation(COLOR_3D_VIZ_COORD + rotation * Vec3::Z * self.distance)
+            .with_rotation(rotation)
+    }
+
+    fn lerp(&self, other: &Orbit, t: f32) -> Orbit {
+        Orbit {
+            yaw: self.yaw.lerp(other.yaw, t),
+            pitch: self.pitch.lerp(other.pitch, t),
+            distance: self.distance.lerp(other.distance, t),
+        }
+    }
+}
+
+// preset (yaw, pitch)
+const VIEW_TOP: (f32, f32) = (0., -FRAC_PI_2);
+const VIEW_FRONT: (f32, f32) = (0., 0.);
+const VIEW_SIDE: (f32, f32) = (FRAC_PI_2, 0.);
+// matches the perspective fov of PI / 4, so toggling keeps the size
+const ORTHO_HEIGHT_PER_DISTANCE: f32 = 0.828_427_1;
+
 #[derive(Component)]
 pub struct MeshControlConf {
     pub v_pitch: f32,
     pub v_yaw: f32,
-    pub orbit_distance: f32,
     pub pitch_max: f32,
     pub pitch_min: f32,
+    pub zoom_step: f32,
+    pub distance_min: f32,
+    pub distance_max: f32,
+    /// how quickly the camera catches up with the input, per second
+    pub damping: f32,
+    pub orthographic: bool,
+    /// where the camera is heading
+    pub target: Orbit,
+    /// where the camera is
+    pub current: Orbit,
+    orbiting: bool,
 }
 impl Default for MeshControlConf {
     fn default() -> Self {
+        let orbit = Orbit::from_transform(&viz3d_cam_transform());
         MeshControlConf {
             v_pitch: 0.01,
             v_yaw: 0.01,
-            // 2 * sqrt(3)
-            orbit_distance: 3.464_101_6,
-            pitch_max: 0.0,
-            pitch_min: -0.9,
+            pitch_max: FRAC_PI_2,
+            pitch_min: -FRAC_PI_2,
+            zoom_step: 1.1,
+            distance_min: 0.5,
+            distance_max: 20.,
+            damping: 15.,
+            orthographic: false,
+            target: orbit,
+            current: orbit,
+            orbiting: false,
         }
     }
 }
+impl MeshControlConf {
+    /// turn towards a preset without spinning around more than half a turn
+    fn look_from(&mut self, (yaw, pitch): (f32, f32)) {
+        let turn = (yaw - self.current.yaw + PI).rem_euclid(TAU) - PI;
+        self.target.yaw = self.current.yaw + turn;
+        self.target.pitch = pitch;
+    }
+}
+
+#[derive(Component)]
+pub struct ImageViewConf {
+    /// keep the whole image in view, cleared by zooming or panning
+    pub fit: bool,
+    pub zoom_step: f32,
+    /// size of the image mesh in world units
+    pub size: Vec2,
+    /// size of the image in pixels
+    pub texels: Vec2,
+    panning: bool,
+}
+impl ImageViewConf {
+    pub fn new(size: Vec2, texels: Vec2) -> Self {
+        ImageViewConf {
+            fit: true,
+            zoom_step: 1.25,
+            size,
+            texels,
+            panning: false,
+        }
+    }
+
+    /// scale at which one image pixel is one physical pixel
+    fn actual_scale(&self, scale_factor: f32) -> f32 {
+        self.size.y / self.texels.y * scale_factor
+    }
+
+    /// world position to image pixels, y pointing down
+    pub fn to_texel(&self, world: Vec2) -> Vec2 {
+        (world / self.size * Vec2::new(1., -1.) + 0.5) * self.texels
+    }
+
+    /// image pixels to world position
+    pub fn to_world(&self, texel: Vec2) -> Vec2 {
+        (texel / self.texels - 0.5) * Vec2::new(1., -1.) * self.size
+    }
+}
 
 #[derive(Resource)]
 pub struct ColorParam {
@@ -53,108 +177,550 @@ impl KbdCooldown {
 // https://bevyengine.org/examples/camera/camera-orbit/
 pub fn control_blob(
     // initialized when setting up scene
-    mut blob: Query<(&mut Transform, &MeshControlConf)>,
+    mut blob: Query<(&mut Transform, &mut Projection, &mut MeshControlConf)>,
     window: Single<&Window>,
-    mouse: Res<ButtonInput<MouseButton>>,
+    layout: Res<Layout>,
+    actions: Actions,
     accumulated_mouse_motion: Res<AccumulatedMouseMotion>,
+    time: Res<Time>,
 ) {
-    let Ok((mut transform, conf)) = blob.single_mut() else {
+    let Ok((mut transform, mut projection, mut conf)) = blob.single_mut() else {
         return;
     };
 
-    let delta = accumulated_mouse_motion.delta;
-    if delta == Vec2::ZERO {
-        return;
+    if actions.just_pressed(Action::Reset) {
+        conf.target = Orbit::from_transform(&viz3d_cam_transform());
+    }
+    if actions.just_pressed(Action::ViewTop) {
+        conf.look_from(VIEW_TOP);
+    }
+    if actions.just_pressed(Action::ViewFront) {
+        conf.look_from(VIEW_FRONT);
+    }
+    if actions.just_pressed(Action::ViewSide) {
+        conf.look_from(VIEW_SIDE);
+    }
+    if actions.just_pressed(Action::ToggleProjection) {
+        conf.orthographic = !conf.orthographic;
     }
 
-    let size = window.size();
-    let pos = window.cursor_position().unwrap();
-    let x_threshold = size.x * IMG_VIEW_W_RATIO;
-    let y_threshold = size.y * VIZ3D_H_RATIO;
+    // cursor may have left the window in windowed mode
+    let hovered = window
+        .cursor_position()
+        .is_some_and(|pos| layout.hit(pos / window.size()) == Some(CamViewPort::Viz3d));
 
-    // check in bound
-    if mouse.pressed(MouseButton::Left) && pos.x > x_threshold && pos.y < y_threshold {
+    // orbit only if the drag started on the 3d viz
+    if actions.just_pressed(Action::Orbit) {
+        conf.orbiting = hovered && layout.dragging.is_none();
+    } else if !actions.pressed(Action::Orbit) {
+        conf.orbiting = false;
+    }
+    let delta = accumulated_mouse_motion.delta;
+    if conf.orbiting {
         // 3d polar coordinate
-        let (mut yaw, mut pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
-        yaw -= delta.x * conf.v_yaw;
-        pitch = (pitch - delta.y * conf.v_pitch).clamp(conf.pitch_min, conf.pitch_max);
+        conf.target.yaw -= delta.x * conf.v_yaw;
+        conf.target.pitch =
+            (conf.target.pitch - delta.y * conf.v_pitch).clamp(conf.pitch_min, conf.pitch_max);
+    }
 
-        transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll);
+    if hovered {
+        let zoom = if actions.just_pressed(Action::ZoomIn) {
+            1. / conf.zoom_step
+        } else if actions.just_pressed(Action::ZoomOut) {
+            conf.zoom_step
+        } else {
+            1.
+        };
+        conf.target.distance =
+            (conf.target.distance * zoom).clamp(conf.distance_min, conf.distance_max);
+    }
 
-        // Adjust the translation to maintain the correct orientation toward the orbit target.
-        transform.translation = Transform::from_translation(
-            COLOR_3D_VIZ_COORD - transform.forward() * conf.orbit_distance,
-        )
-        .translation;
+    // ease towards the target
+    let t = 1. - (-conf.damping * time.delta_secs()).exp();
+    conf.current = conf.current.lerp(&conf.target, t);
+    *transform = conf.current.transform();
+
+    match (conf.orthographic, projection.as_mut()) {
+        (true, Projection::Orthographic(ortho)) => {
+            ortho.scaling_mode = ScalingMode::FixedVertical {
+                viewport_height: conf.current.distance * ORTHO_HEIGHT_PER_DISTANCE,
+            };
+        }
+        (true, _) => {
+            *projection = Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical {
+                    viewport_height: conf.current.distance * ORTHO_HEIGHT_PER_DISTANCE,
+                },
+                ..OrthographicProjection::default_3d()
+            });
+        }
+        (false, Projection::Perspective(_)) => {}
+        (false, _) => *projection = Projection::Perspective(default()),
     }
 }
 
 pub fn change_param<A: CSpaceProvider>(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mouse: Res<ButtonInput<MouseButton>>,
+    actions: Actions,
     window: Single<&Window>,
+    layout: Res<Layout>,
     mut param: ResMut<ColorParam>,
     time: Res<Time>,
     mut p: ResMut<A>,
     img: Option<Res<Background>>,
     loader: Query<(Entity, &ImageLoader)>,
-    // queries for entities that needs to be updated
-    mut img_canvas: Query<(&mut MeshMaterial2d<A::FilterMaterial>, &ImageCanvas)>,
-    mut viz2d_canvas: Query<(&mut MeshMaterial2d<A::Viz2dMaterial>, &Viz2DCanvas)>,
-    mut viz3d_mesh: Query<(
-        (&mut MeshMaterial3d<A::Viz3dMaterial>, &mut Mesh3d),
-        &Viz3DMesh,
-    )>,
-    mut text: Query<&mut Text2d>,
-    // entity managers
-    mut img_filters: ResMut<Assets<A::FilterMaterial>>,
-    mut viz2d_materials: ResMut<Assets<A::Viz2dMaterial>>,
-    mut viz3d_materials: ResMut<Assets<A::Viz3dMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
 ) {
     if !param.cooldown.finished(time) || !loader.is_empty() || img.is_none() {
         return;
     }
 
     // response to clicks on viz2d
-    if mouse.just_pressed(MouseButton::Left) {
-        let size = window.size();
-        let pos = window.cursor_position().unwrap();
-        let x_threshold = size.x * IMG_VIEW_W_RATIO;
-        let y_threshold = size.y * VIZ3D_H_RATIO;
-
-        if pos.x > x_threshold && pos.y > y_threshold {
-            p.set((pos.x - x_threshold) / (size.x - x_threshold));
+    if actions.just_pressed(Action::Pick)
+        && layout.dragging.is_none()
+        && let Some(pos) = window.cursor_position()
+        && let Some(rect) = layout.rect(CamViewPort::Viz2d)
+    {
+        let pos = pos / window.size();
+        if rect.contains(pos) {
+            p.set((pos.x - rect.min.x) / rect.width());
         }
     }
 
-    let change = if keyboard.pressed(KeyCode::ShiftLeft) {
+    let change = if actions.pressed(Action::CoarseStep) {
         param.delta * 10.
     } else {
         param.delta
     };
 
-    if keyboard.pressed(KeyCode::KeyJ) {
+    // the other panels use the wheel for zooming
+    let wheel = window
+        .cursor_position()
+        .is_some_and(|pos| layout.hit(pos / window.size()) == Some(CamViewPort::Viz2d));
+    let pressed = |a| {
+        if wheel {
+            actions.pressed(a)
+        } else {
+            actions.button_pressed(a)
+        }
+    };
+    let just_pressed = |a| {
+        if wheel {
+            actions.just_pressed(a)
+        } else {
+            actions.button_just_pressed(a)
+        }
+    };
+
+    // fine stepping only moves once per press
+    let (incr, decr) = if actions.pressed(Action::FineStep) {
+        (
+            just_pressed(Action::Increment),
+            just_pressed(Action::Decrement),
+        )
+    } else {
+        (pressed(Action::Increment), pressed(Action::Decrement))
+    };
+
+    if actions.just_pressed(Action::Reset) {
+        // back to the initial slice
+        p.set(1.);
+    } else if decr {
         // decrement param
         p.decr(change);
         param.cooldown.reset();
-    } else if keyboard.pressed(KeyCode::KeyK) {
+    } else if incr {
         // increment param
         p.incr(change);
         param.cooldown.reset();
     }
+}
+
+/// apart from change_param, so that the stepping cooldown doesn't swallow a press
+pub fn change_scaling(actions: Actions, mut scaling: ResMut<Scaling>) {
+    if actions.just_pressed(Action::CycleScale) {
+        scaling.cycle();
+    }
+    if actions.just_pressed(Action::CycleNormalization) {
+        scaling.cycle_normalization();
+    }
+}
+
+pub fn toggle_fullscreen(actions: Actions, mut window: Single<&mut Window, With<PrimaryWindow>>) {
+    if actions.just_pressed(Action::ToggleFullscreen) {
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+            _ => WindowMode::Windowed,
+        };
+    }
+}
+
+pub fn toggle_gamut(actions: Actions, mut overlay: Query<&mut Visibility, With<GamutOverlay>>) {
+    if actions.just_pressed(Action::ToggleGamut) {
+        for mut visibility in &mut overlay {
+            visibility.toggle_visible_hidden();
+        }
+    }
+}
+
+/// 8-bit images are analyzed as they are, only the others have an exposure
+pub fn change_exposure(
+    actions: Actions,
+    hdr: Option<Res<HdrSource>>,
+    mut exposure: ResMut<Exposure>,
+) {
+    if hdr.is_none() {
+        return;
+    }
+    if actions.just_pressed(Action::ExposureUp) {
+        exposure.stops += EXPOSURE_STEP;
+    }
+    if actions.just_pressed(Action::ExposureDown) {
+        exposure.stops -= EXPOSURE_STEP;
+    }
+    if actions.just_pressed(Action::CycleToneMap) {
+        exposure.tonemap = exposure.tonemap.next();
+    }
+}
+
+/// step through the frames with the keys, ten at a time with the coarse step held, or pick one
+/// on the timeline
+pub fn change_frame(
+    actions: Actions,
+    frames: Option<ResMut<Frames>>,
+    strip: Query<(&Interaction, &RelativeCursorPosition), With<TimelineStrip>>,
+) {
+    let Some(mut frames) = frames else {
+        return;
+    };
+    let step = if actions.pressed(Action::CoarseStep) {
+        10
+    } else {
+        1
+    };
+    let mut frame = frames.current;
+    if actions.just_pressed(Action::NextFrame) {
+        frame = frames.clamped(frame as isize + step);
+    }
+    if actions.just_pressed(Action::PrevFrame) {
+        frame = frames.clamped(frame as isize - step);
+    }
+    // held down on the strip drags through the frames
+    for (interaction, cursor) in &strip {
+        if *interaction == Interaction::Pressed
+            && let Some(pos) = cursor.normalized
+        {
+            frame = frames.clamped((pos.x * frames.images.len() as f32).floor() as isize);
+        }
+    }
+    // only changes trigger the statistics
+    if frame != frames.current {
+        frames.current = frame;
+    }
+}
+
+/// hand the image on the clipboard to replace_image, which takes it once the scene is drawn
+pub fn paste_image(mut commands: Commands, actions: Actions) {
+    if !actions.just_pressed(Action::Paste) {
+        return;
+    }
+    match decoded::read_clipboard() {
+        Ok(image) => commands.insert_resource(Decoded(image)),
+        Err(e) => eprintln!("warning: {}", e),
+    }
+}
+
+pub fn change_scheme(actions: Actions, mut terminal: ResMut<TerminalScheme>) {
+    if actions.just_pressed(Action::ToggleScheme) {
+        terminal.shown = !terminal.shown;
+    }
+    if actions.just_pressed(Action::CopyScheme)
+        && let Err(e) = terminal.copy()
+    {
+        eprintln!("warning: {}", e);
+    }
+}
+
+pub fn change_quantization(actions: Actions, mut quantization: ResMut<Quantization>) {
+    if actions.just_pressed(Action::ToggleQuantize) {
+        quantization.toggle();
+    }
+    if actions.just_pressed(Action::ToggleErrorMap) {
+        quantization.toggle_error();
+    }
+}
+
+/// the timeline is over the image, clicks on it are not for the image view
+fn over_timeline(strip: &Query<&Interaction, With<TimelineStrip>>) -> bool {
+    strip.iter().any(|i| *i != Interaction::None)
+}
 
-    // apply change, original item substituted
-    if p.is_changed() {
-        // update image filter
-        img_canvas.single_mut().unwrap().0 .0 = img_filters.add(p.get_filter());
-        // update viz2d current color indicator
-        viz2d_canvas.single_mut().unwrap().0 .0 = viz2d_materials.add(p.get_viz2d_material());
-        // update viz3d material
-        viz3d_mesh.single_mut().unwrap().0 .0 .0 = viz3d_materials.add(p.get_viz3d_material());
-        // update viz3d mesh
-        viz3d_mesh.single_mut().unwrap().0 .1 .0 = meshes.add(p.create_mesh(&img.unwrap().0));
-        // update param banner
-        text.single_mut().unwrap().0 = format!("{}", p.current());
+pub fn change_simulation(actions: Actions, mut simulation: ResMut<Simulation>) {
+    if actions.just_pressed(Action::CycleCvd) {
+        simulation.cvd = Deficiency::cycle(simulation.cvd);
+    }
+    if actions.just_pressed(Action::ToggleCvdAnalysis) {
+        simulation.analysis = !simulation.analysis;
+    }
+}
+
+pub fn change_harmonies(actions: Actions, mut harmonies: ResMut<Harmonies>) {
+    if actions.just_pressed(Action::CycleHarmony) {
+        harmonies.harmony = Harmony::cycle(harmonies.harmony);
+    }
+    if actions.just_pressed(Action::ToggleHarmonyBase) {
+        harmonies.dominant = !harmonies.dominant;
+    }
+}
+
+pub fn drag_splitters(actions: Actions, window: Single<&Window>, mut layout: ResMut<Layout>) {
+    if !actions.pressed(Action::Resize) {
+        if layout.dragging.is_some() {
+            layout.dragging = None;
+        }
+        return;
+    }
+
+    let Some(pos) = window.cursor_position() else {
+        return;
+    };
+    if actions.just_pressed(Action::Resize) {
+        layout.dragging = layout.splitter_at(pos, window.size());
+    }
+    if layout.dragging.is_some() {
+        layout.drag_to(pos / window.size());
+    }
+}
+
+pub fn arrange_panels(actions: Actions, window: Single<&Window>, mut layout: ResMut<Layout>) {
+    if actions.just_pressed(Action::Maximize) {
+        // maximize the panel under the cursor, or restore
+        layout.maximized = match layout.maximized {
+            Some(_) => None,
+            None => window
+                .cursor_position()
+                .and_then(|pos| layout.hit(pos / window.size())),
+        };
+    }
+    if actions.just_pressed(Action::SwapPanels) {
+        layout.mirrored = !layout.mirrored;
+    }
+}
+
+pub fn control_image_view(
+    mut camera: Query<(&mut Transform, &mut Projection, &mut ImageViewConf)>,
+    window: Single<&Window>,
+    layout: Res<Layout>,
+    actions: Actions,
+    accumulated_mouse_motion: Res<AccumulatedMouseMotion>,
+    selection: Res<Selection>,
+    strip: Query<&Interaction, With<TimelineStrip>>,
+) {
+    let Ok((mut transform, mut projection, mut conf)) = camera.single_mut() else {
+        return;
+    };
+    let Projection::Orthographic(ortho) = projection.as_mut() else {
+        return;
+    };
+    let Some(rect) = layout.rect(CamViewPort::ImageFilter) else {
+        return;
+    };
+    let panel = rect.size() * window.size();
+    if panel.x <= 0. || panel.y <= 0. {
+        return;
+    }
+
+    if actions.just_pressed(Action::Reset) || actions.just_pressed(Action::FitView) {
+        conf.fit = true;
+    }
+    if conf.fit {
+        // follow panel resizes
+        ortho.scale = (conf.size / panel).max_element();
+        transform.translation = Vec2::ZERO.extend(transform.translation.z);
+    }
+    if actions.just_pressed(Action::ActualSize) {
+        ortho.scale = conf.actual_scale(window.scale_factor());
+        conf.fit = false;
+    }
+
+    let hovered = window
+        .cursor_position()
+        .filter(|pos| layout.hit(pos / window.size()) == Some(CamViewPort::ImageFilter))
+        .filter(|_| !over_timeline(&strip));
+
+    // pan only if the drag started on the image and doesn't draw a selection
+    let selecting = selection.tool.is_some() && actions.button_pressed(Action::Select);
+    if actions.just_pressed(Action::Pan) {
+        conf.panning = hovered.is_some() && layout.dragging.is_none() && !selecting;
+    } else if !actions.pressed(Action::Pan) {
+        conf.panning = false;
+    }
+    let delta = accumulated_mouse_motion.delta;
+    if conf.panning && delta != Vec2::ZERO {
+        transform.translation.x -= delta.x * ortho.scale;
+        transform.translation.y += delta.y * ortho.scale;
+        conf.fit = false;
+    }
+
+    let Some(pos) = hovered else {
+        return;
+    };
+    let zoom = if actions.just_pressed(Action::ZoomIn) {
+        1. / conf.zoom_step
+    } else if actions.just_pressed(Action::ZoomOut) {
+        conf.zoom_step
+    } else {
+        return;
+    };
+
+    // keep the point under the cursor in place, y points up in world space
+    let offset = (pos - rect.center() * window.size()) * Vec2::new(1., -1.);
+    let anchor = transform.translation.xy() + offset * ortho.scale;
+    let min_scale = conf.actual_scale(window.scale_factor()) / 16.;
+    let max_scale = (conf.size / panel).max_element() * 4.;
+    ortho.scale = (ortho.scale * zoom).clamp(min_scale, max_scale.max(min_scale));
+    transform.translation = (anchor - offset * ortho.scale).extend(transform.translation.z);
+    conf.fit = false;
+}
+
+/// a window position in image pixels, given the image view's panel, possibly outside of the image
+fn texel_at(
+    pos: Vec2,
+    window: &Window,
+    rect: Rect,
+    transform: &Transform,
+    ortho: &OrthographicProjection,
+    conf: &ImageViewConf,
+) -> Vec2 {
+    let offset = (pos - rect.center() * window.size()) * Vec2::new(1., -1.);
+    conf.to_texel(transform.translation.xy() + offset * ortho.scale)
+}
+
+/// add the color under the cursor to the swatches compared in the contrast panel, as analyzed
+pub fn pick_swatch(
+    camera: Query<(&Transform, &Projection, &ImageViewConf)>,
+    window: Single<&Window>,
+    layout: Res<Layout>,
+    actions: Actions,
+    img: Option<Res<Background>>,
+    strip: Query<&Interaction, With<TimelineStrip>>,
+    mut swatches: ResMut<Swatches>,
+) {
+    if actions.just_pressed(Action::ClearSwatches) && !swatches.0.is_empty() {
+        swatches.0.clear();
+    }
+    if !actions.just_pressed(Action::PickSwatch) {
+        return;
+    }
+    let (Ok((transform, projection, conf)), Some(img)) = (camera.single(), img) else {
+        return;
+    };
+    let Projection::Orthographic(ortho) = projection else {
+        return;
+    };
+    let (Some(pos), Some(rect)) = (
+        window.cursor_position(),
+        layout.rect(CamViewPort::ImageFilter),
+    ) else {
+        return;
+    };
+    if layout.hit(pos / window.size()) != Some(CamViewPort::ImageFilter) || over_timeline(&strip) {
+        return;
+    }
+    let texel = texel_at(pos, &window, rect, transform, ortho, conf).floor();
+    if texel.cmplt(Vec2::ZERO).any() || texel.cmpge(conf.texels).any() {
+        return;
+    }
+    if let Ok(c) = img.0.get_color_at(texel.x as u32, texel.y as u32) {
+        let c: Srgba = c.into();
+        swatches.add(Srgb::new(c.red, c.green, c.blue));
+    }
+}
+
+/// minimal distance between lasso vertices, in image pixels
+const LASSO_STEP: f32 = 2.;
+
+/// draw, close and clear the region of interest on the image
+pub fn control_selection(
+    camera: Query<(&Transform, &Projection, &ImageViewConf)>,
+    window: Single<&Window>,
+    layout: Res<Layout>,
+    actions: Actions,
+    mut selection: ResMut<Selection>,
+    mut roi: ResMut<Roi>,
+    strip: Query<&Interaction, With<TimelineStrip>>,
+) {
+    if actions.just_pressed(Action::CycleSelectTool) {
+        selection.cycle_tool();
+    }
+    if actions.just_pressed(Action::ClearSelection) {
+        selection.drawing.clear();
+        if roi.0.is_some() {
+            roi.0 = None;
+        }
+    }
+
+    let Ok((transform, projection, conf)) = camera.single() else {
+        return;
+    };
+    let Projection::Orthographic(ortho) = projection else {
+        return;
+    };
+    let (Some(tool), Some(rect)) = (selection.tool, layout.rect(CamViewPort::ImageFilter)) else {
+        if selection.cursor.is_some() {
+            selection.cursor = None;
+        }
+        return;
+    };
+
+    // cursor in image pixels, kept on the image so shapes can be dragged past its edges
+    let hovered = window.cursor_position().is_some_and(|pos| {
+        layout.hit(pos / window.size()) == Some(CamViewPort::ImageFilter)
+            && layout.dragging.is_none()
+            && !over_timeline(&strip)
+    });
+    let cursor = window.cursor_position().map(|pos| {
+        texel_at(pos, &window, rect, transform, ortho, conf).clamp(Vec2::ZERO, conf.texels)
+    });
+    // compared first, as writing marks the selection changed and rewrites its hint every frame
+    let shown = cursor.filter(|_| hovered);
+    if selection.cursor != shown {
+        selection.cursor = shown;
+    }
+
+    let started = hovered && actions.button_just_pressed(Action::Select);
+    let held = actions.button_pressed(Action::Select);
+    let lasso_moved = cursor
+        .zip(selection.drawing.last())
+        .is_some_and(|(c, last)| last.distance(c) >= LASSO_STEP);
+    let mut close = false;
+    match (tool, cursor) {
+        (SelectTool::Rect | SelectTool::Lasso, Some(c)) if started => {
+            selection.drawing = vec![c];
+        }
+        (SelectTool::Rect, Some(c)) if held && !selection.drawing.is_empty() => {
+            let a = selection.drawing[0];
+            selection.drawing = vec![a, Vec2::new(c.x, a.y), c, Vec2::new(a.x, c.y)];
+        }
+        (SelectTool::Lasso, Some(c)) if held && lasso_moved => {
+            selection.drawing.push(c);
+        }
+        (SelectTool::Rect | SelectTool::Lasso, _) if !held => {
+            // released
+            close = !selection.drawing.is_empty();
+        }
+        (SelectTool::Polygon, Some(c)) if started => {
+            selection.drawing.push(c);
+        }
+        _ => {}
+    }
+    if tool == SelectTool::Polygon && actions.just_pressed(Action::CloseSelection) {
+        close = true;
+    }
+
+    if close {
+        let outline = std::mem::take(&mut selection.drawing);
+        // a click without a drag selects nothing, and keeps the previous selection
+        if let Some(region) = Region::new(outline, conf.texels) {
+            roi.0 = Some(region);
+        }
     }
 }
@@ -0,0 +1,98 @@
+use std::{fmt::Display, str::FromStr};
+
+use palette::LinSrgb;
+
+use crate::Error;
+
+/// A color vision deficiency, simulated at full severity to check colors stay distinguishable
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Deficiency {
+    /// no long-wavelength cones, reds look dark and close to greens
+    Protanopia,
+    /// no medium-wavelength cones, reds and greens look alike
+    Deuteranopia,
+    /// no short-wavelength cones, blues and greens look alike
+    Tritanopia,
+    /// no cones at all, only luminance is left
+    Achromatopsia,
+}
+
+/// Machado, Oliveira and Fernandes (2009) at severity 1.0, rows applied to linear sRGB
+const PROTANOPIA: [[f32; 3]; 3] = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+const DEUTERANOPIA: [[f32; 3]; 3] = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+const TRITANOPIA: [[f32; 3]; 3] = [
+    [1.255528, -0.076749, -0.178779],
+    [-0.078411, 0.930809, 0.147602],
+    [0.004733, 0.691367, 0.303900],
+];
+/// share of each channel in the luminance of a linear sRGB color
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+impl Deficiency {
+    pub const ALL: [Deficiency; 4] = [
+        Deficiency::Protanopia,
+        Deficiency::Deuteranopia,
+        Deficiency::Tritanopia,
+        Deficiency::Achromatopsia,
+    ];
+
+    /// lowercase name, as taken by FromStr
+    pub fn name(self) -> &'static str {
+        match self {
+            Deficiency::Protanopia => "protanopia",
+            Deficiency::Deuteranopia => "deuteranopia",
+            Deficiency::Tritanopia => "tritanopia",
+            Deficiency::Achromatopsia => "achromatopsia",
+        }
+    }
+
+    /// the one after `current` in ALL, None after the last so that cycling passes by normal
+    /// vision
+    pub fn cycle(current: Option<Deficiency>) -> Option<Deficiency> {
+        match current {
+            None => Some(Deficiency::ALL[0]),
+            Some(d) => {
+                let i = Deficiency::ALL.iter().position(|a| *a == d).unwrap();
+                Deficiency::ALL.get(i + 1).copied()
+            }
+        }
+    }
+
+    /// the color as seen with the deficiency, within [0,1]
+    pub fn simulate(self, rgb: LinSrgb) -> LinSrgb {
+        let c = [rgb.red, rgb.green, rgb.blue];
+        let dot = |row: [f32; 3]| (row[0] * c[0] + row[1] * c[1] + row[2] * c[2]).clamp(0., 1.);
+        let [r, g, b] = match self {
+            Deficiency::Protanopia => PROTANOPIA.map(dot),
+            Deficiency::Deuteranopia => DEUTERANOPIA.map(dot),
+            Deficiency::Tritanopia => TRITANOPIA.map(dot),
+            Deficiency::Achromatopsia => [dot(LUMINANCE); 3],
+        };
+        LinSrgb::new(r, g, b)
+    }
+}
+
+impl Display for Deficiency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Deficiency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Deficiency::ALL
+            .into_iter()
+            .find(|d| d.name() == s)
+            .ok_or_else(|| Error::UnknownDeficiency(s.to_string()))
+    }
+}
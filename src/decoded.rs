@@ -0,0 +1,61 @@
+use std::io::{IsTerminal, Read};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+/// FILE that reads the image from stdin
+pub const STDIN: &str = "-";
+
+/// An image that didn't come from a file, decoded by sefenbu instead of bevy's loaders. Taken by
+/// setup_scene_pre at startup and by replace_image after a paste
+#[derive(Resource)]
+pub struct Decoded(pub Image);
+
+/// All of stdin, e.g. piped from a screenshot tool
+pub fn read_stdin() -> Result<Vec<u8>, String> {
+    let err = |e: &dyn std::fmt::Display| format!("Cannot read image from stdin: {}", e);
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(err(&"nothing is piped in"));
+    }
+    let mut bytes = vec![];
+    stdin.read_to_end(&mut bytes).map_err(|e| err(&e))?;
+    if bytes.is_empty() {
+        return Err(err(&"stdin is empty"));
+    }
+    Ok(bytes)
+}
+
+/// An image file in memory, in any of the formats sefenbu opens, animations give their first
+/// frame
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let decoded = image::load_from_memory(bytes)
+        .map_err(|e| format!("Cannot decode image from stdin: {}", e))?;
+    Ok(Image::from_dynamic(
+        decoded,
+        true,
+        RenderAssetUsages::default(),
+    ))
+}
+
+/// The image on the clipboard, always 8-bit sRGB
+pub fn read_clipboard() -> Result<Image, String> {
+    let err = |e: arboard::Error| format!("Cannot read image from the clipboard: {}", e);
+    let data = arboard::Clipboard::new()
+        .and_then(|mut c| c.get_image())
+        .map_err(err)?;
+    Ok(Image::new(
+        Extent3d {
+            width: data.width as u32,
+            height: data.height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data.bytes.into_owned(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    ))
+}
@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, HashMap};
+
+use palette::Srgb;
+
+use crate::{
+    Error,
+    space::{Bin, Space},
+};
+
+/// saturation times value below which colors count as achromatic, unless told otherwise
+pub const DEFAULT_ACHROMATIC: f32 = 0.02;
+
+/// How the colors of an image spread over the bins of a space
+#[derive(Clone, Debug)]
+pub struct Distribution {
+    space: Space,
+    /// weighted pixel count per bin, of the pixels with a meaningful hue
+    chromatic: HashMap<Bin, f64>,
+    /// weighted pixel count per bin, of the pixels too gray for their hue to mean anything
+    achromatic: HashMap<Bin, f64>,
+    total: f64,
+}
+
+impl Distribution {
+    /// Analyze 8-bit RGBA pixels, row by row. Fully transparent pixels are left out and colors
+    /// below DEFAULT_ACHROMATIC are achromatic
+    pub fn from_rgba8(width: u32, height: u32, pixels: &[u8], space: Space) -> Result<Self, Error> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(Error::SizeMismatch {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+        let colors = pixels
+            .chunks_exact(4)
+            .filter(|p| p[3] > 0)
+            .map(|p| (Srgb::new(p[0], p[1], p[2]).into_format(), 1.));
+        Ok(Distribution::from_weighted(
+            space,
+            DEFAULT_ACHROMATIC,
+            colors,
+        ))
+    }
+
+    /// Analyze colors along with how much each of them counts, e.g. their opacity. `achromatic`
+    /// is the saturation times value below which colors have no hue, 0 to disable
+    pub fn from_weighted(
+        space: Space,
+        achromatic: f32,
+        colors: impl IntoIterator<Item = (Srgb, f32)>,
+    ) -> Self {
+        let mut distribution = Distribution {
+            space,
+            chromatic: HashMap::new(),
+            achromatic: HashMap::new(),
+            total: 0.,
+        };
+        for (rgb, weight) in colors {
+            let coords = space.coords(rgb);
+            let bins = if space.is_achromatic(coords, achromatic) {
+                &mut distribution.achromatic
+            } else {
+                &mut distribution.chromatic
+            };
+            *bins.entry(space.bin(coords)).or_insert(0.) += weight as f64;
+            distribution.total += weight as f64;
+        }
+        distribution
+    }
+
+    pub fn space(&self) -> Space {
+        self.space
+    }
+
+    /// sum of all weights
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    /// share of the weight in each slice, achromatic colors left out
+    pub fn histogram(&self) -> Vec<(f32, f32)> {
+        let mut slices: BTreeMap<i64, f64> = BTreeMap::new();
+        for ((slice, _), count) in &self.chromatic {
+            *slices.entry(*slice).or_insert(0.) += count;
+        }
+        let total = self.total.max(f64::MIN_POSITIVE);
+        slices
+            .iter()
+            .map(|(x, y)| (*x as f32, (*y / total) as f32))
+            .collect()
+    }
+
+    /// share of the weight without a meaningful hue
+    pub fn achromatic_ratio(&self) -> f32 {
+        // not sum(), which is -0 when empty
+        let achromatic = self.achromatic.values().fold(0., |sum, count| sum + count);
+        (achromatic / self.total.max(f64::MIN_POSITIVE)) as f32
+    }
+
+    /// weighted pixel count per bin, achromatic colors in their own hue bins if kept
+    pub fn bins(&self, keep_achromatic: bool) -> HashMap<Bin, f64> {
+        let mut bins = self.chromatic.clone();
+        if keep_achromatic {
+            for (bin, count) in &self.achromatic {
+                *bins.entry(*bin).or_insert(0.) += count;
+            }
+        }
+        bins
+    }
+
+    /// weighted pixel count per data point of the slice at `value`, which covers its own bin and
+    /// the one below
+    pub fn slice(&self, value: f32, keep_achromatic: bool) -> BTreeMap<(i64, i64), f64> {
+        let delta = self.space.binning().delta;
+        let top = (value / delta) as i64 * (delta as i64);
+        let mut stats = BTreeMap::new();
+        for ((slice, data), count) in self.bins(keep_achromatic) {
+            if slice == top || slice == top - delta as i64 {
+                *stats.entry(data).or_insert(0.) += count;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn total_above_f32_precision() {
+        // 2^24 + 1 is the first integer an f32 can't hold, so every unit weight past it was lost
+        let red = Srgb::new(1., 0., 0.);
+        let colors =
+            std::iter::once((red, 16_777_216.)).chain(std::iter::repeat_n((red, 1.), 1000));
+        let distribution = Distribution::from_weighted(Space::Okhsv, DEFAULT_ACHROMATIC, colors);
+        assert_eq!(distribution.total(), 16_778_216.);
+        let share: f32 = distribution.histogram().iter().map(|(_, y)| y).sum();
+        assert!((share - 1.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::image::ImageLoader;
+
+/// OpenEXR and Radiance HDR, which bevy has loaders of their own for
+const FLOAT_FORMATS: [&str; 2] = ["exr", "hdr"];
+
+/// Extensions of the images sefenbu can open, sorted
+pub fn supported_extensions() -> Vec<&'static str> {
+    let mut extensions: Vec<&str> = ImageLoader::SUPPORTED_FILE_EXTENSIONS
+        .iter()
+        .copied()
+        .chain(FLOAT_FORMATS)
+        .collect();
+    #[cfg(feature = "avif")]
+    extensions.extend(avif::EXTENSIONS);
+    extensions.sort_unstable();
+    extensions.dedup();
+    extensions
+}
+
+/// shown at the end of --help
+pub fn supported_help() -> String {
+    format!("Supported images: {}", supported_extensions().join(", "))
+}
+
+/// AVIF decoding needs the dav1d library, so it is only built with the avif feature
+#[cfg(feature = "avif")]
+pub mod avif {
+    use bevy::{
+        asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader},
+        prelude::*,
+    };
+
+    pub const EXTENSIONS: [&str; 1] = ["avif"];
+
+    #[derive(Default)]
+    pub struct AvifLoader;
+
+    impl AssetLoader for AvifLoader {
+        type Asset = Image;
+        type Settings = ();
+        type Error = std::io::Error;
+
+        async fn load(
+            &self,
+            reader: &mut dyn Reader,
+            _settings: &(),
+            _load_context: &mut LoadContext<'_>,
+        ) -> Result<Image, Self::Error> {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let decoded = image::load_from_memory_with_format(&bytes, image::ImageFormat::Avif)
+                .map_err(std::io::Error::other)?;
+            Ok(Image::from_dynamic(
+                decoded,
+                true,
+                RenderAssetUsages::default(),
+            ))
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &EXTENSIONS
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use image::{
+    AnimationDecoder, DynamicImage, ImageReader,
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+};
+use palette::{FromColor, Hsl, Hsv, Okhsl, Okhsv, Srgb};
+use sefenbu::{Distribution, Profile, Space};
+
+use crate::{
+    formats,
+    hdr::{self, Exposure},
+};
+
+/// pixels per side sampled from each frame for the timeline
+const TIMELINE_SAMPLES: u32 = 128;
+
+/// The frames of an animation or of a directory of numbered images, all of the same size
+#[derive(Resource)]
+pub struct Frames {
+    /// as decoded, before any conversion
+    pub images: Vec<Image>,
+    /// file of each frame of a directory, empty for an animation
+    pub names: Vec<String>,
+    pub current: usize,
+    /// the texture shown in the image view, see setup_scene_pre
+    pub texture: Handle<Image>,
+}
+
+impl Frames {
+    fn new(images: Vec<Image>, names: Vec<String>) -> Result<Self, String> {
+        let size = images[0].size();
+        if let Some((i, frame)) = images.iter().enumerate().find(|(_, f)| f.size() != size) {
+            let which = names
+                .get(i)
+                .map_or(format!("frame {}", i + 1), |n| format!("'{}'", n));
+            return Err(format!(
+                "{} is {}x{} but the first frame is {}x{}",
+                which,
+                frame.width(),
+                frame.height(),
+                size.x,
+                size.y
+            ));
+        }
+        Ok(Frames {
+            images,
+            names,
+            current: 0,
+            texture: Handle::default(),
+        })
+    }
+
+    /// e.g. "frame 3/120" and the file it came from
+    pub fn label(&self) -> String {
+        let label = format!("frame {}/{}", self.current + 1, self.images.len());
+        match self.names.get(self.current) {
+            Some(name) => format!("{}, {}", label, name),
+            None => label,
+        }
+    }
+
+    /// a frame that exists, the first or last one when out of range
+    pub fn clamped(&self, frame: isize) -> usize {
+        frame.clamp(0, self.images.len() as isize - 1) as usize
+    }
+}
+
+/// The images of a directory in the order of the last number in their names
+pub fn read_sequence(dir: &str) -> Result<Frames, String> {
+    let err = |e: std::io::Error| format!("Cannot read directory '{}': {}", dir, e);
+    let supported = formats::supported_extensions();
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir).map_err(err)? {
+        let path = entry.map_err(err)?.path();
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        if path.is_file() && ext.is_some_and(|ext| supported.contains(&ext)) {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        return Err(format!(
+            "No images in '{}', expected one of {}",
+            dir,
+            supported.join(", ")
+        ));
+    }
+    files.sort_by_cached_key(|path| {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        (frame_number(&name), name)
+    });
+
+    let mut images = vec![];
+    let mut names = vec![];
+    for path in files {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let decoded = ImageReader::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|r| r.with_guessed_format().map_err(|e| e.to_string()))
+            .and_then(|r| r.decode().map_err(|e| e.to_string()))
+            .map_err(|e| format!("Cannot load frame '{}': {}", path.display(), e))?;
+        images.push(Image::from_dynamic(
+            decoded,
+            true,
+            RenderAssetUsages::default(),
+        ));
+        names.push(name);
+    }
+    Frames::new(images, names)
+}
+
+/// The frames of an animated GIF, PNG or WebP, None for any other image, and for animations of a
+/// single frame, which bevy loads like any image
+pub fn read_animation(file: &str) -> Result<Option<Frames>, String> {
+    let err = |e: &dyn std::fmt::Display| format!("Cannot load image '{}': {}", file, e);
+    let reader = || File::open(file).map(BufReader::new).map_err(|e| err(&e));
+    let ext = Path::new(file).extension().and_then(|ext| ext.to_str());
+    let frames = match ext {
+        Some("gif") => GifDecoder::new(reader()?).map(|d| d.into_frames().collect_frames()),
+        Some("png") => {
+            let decoder = PngDecoder::new(reader()?).map_err(|e| err(&e))?;
+            if !decoder.is_apng().map_err(|e| err(&e))? {
+                return Ok(None);
+            }
+            decoder.apng().map(|d| d.into_frames().collect_frames())
+        }
+        Some("webp") => {
+            let decoder = WebPDecoder::new(reader()?).map_err(|e| err(&e))?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            Ok(decoder.into_frames().collect_frames())
+        }
+        _ => return Ok(None),
+    };
+    let frames = frames.and_then(|f| f).map_err(|e| err(&e))?;
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+    let images = frames
+        .into_iter()
+        .map(|frame| {
+            Image::from_dynamic(
+                DynamicImage::ImageRgba8(frame.into_buffer()),
+                true,
+                RenderAssetUsages::default(),
+            )
+        })
+        .collect();
+    Frames::new(images, vec![]).map(Some)
+}
+
+/// last run of digits in a file name, e.g. 12 in "shot_02_0012.png"
+fn frame_number(name: &str) -> Option<u64> {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    stem[start..end].parse().ok()
+}
+
+/// One column per frame and one row per slice of the space, the highest at the top. Each slice
+/// has its color, more opaque the more of the frame is in it, relative to its biggest slice
+pub fn timeline(
+    frames: &[Image],
+    space: Space,
+    achromatic: f32,
+    profile: Option<&Profile>,
+    exposure: Exposure,
+) -> Image {
+    let binning = space.binning();
+    let rows = ((binning.max - binning.min) / binning.delta).round() as usize;
+    let mut data = vec![0; frames.len() * rows * 4];
+    for (x, frame) in frames.iter().enumerate() {
+        let step = (frame.width().max(frame.height()) / TIMELINE_SAMPLES).max(1) as usize;
+        let colors = (0..frame.height())
+            .step_by(step)
+            .flat_map(|j| (0..frame.width()).step_by(step).map(move |i| (i, j)))
+            .filter_map(|(i, j)| hdr::color_at(frame, i, j, profile, exposure))
+            .filter(|(_, alpha)| *alpha > 0.)
+            .map(|(rgb, _)| (rgb, 1.));
+        let histogram = Distribution::from_weighted(space, achromatic, colors).histogram();
+        let peak = histogram
+            .iter()
+            .fold(f32::MIN_POSITIVE, |peak, (_, share)| peak.max(*share));
+        for (slice, share) in histogram {
+            let row = (((slice - binning.min) / binning.delta) as usize).min(rows - 1);
+            let at = ((rows - 1 - row) * frames.len() + x) * 4;
+            let rgb: Srgb<u8> = slice_color(space, slice).into_format();
+            let alpha = (share / peak * 255.).round() as u8;
+            data[at..at + 4].copy_from_slice(&[rgb.red, rgb.green, rgb.blue, alpha]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: frames.len() as u32,
+            height: rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// the hue of a slice at full saturation, or the gray of its lightness
+fn slice_color(space: Space, param: f32) -> Srgb {
+    match space {
+        Space::Okhsv => Srgb::from_color(Okhsv::new(param, 1., 1.)),
+        Space::Hsv => Srgb::from_color(Hsv::new(param, 1., 1.)),
+        Space::Okhsl => Srgb::from_color(Okhsl::new(0., 0., param / 100.)),
+        Space::Hsl => Srgb::from_color(Hsl::new(0., 0., param / 100.)),
+    }
+}
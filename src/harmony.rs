@@ -0,0 +1,124 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{Error, Space};
+
+/// Hues that go together with a base hue, by their distance on the color wheel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Harmony {
+    /// the opposite hue
+    Complementary,
+    /// the two hues a third of the wheel away
+    Triadic,
+    /// the two neighbours of the opposite hue
+    SplitComplementary,
+    /// the two neighbours of the base hue
+    Analogous,
+}
+
+impl Harmony {
+    pub const ALL: [Harmony; 4] = [
+        Harmony::Complementary,
+        Harmony::Triadic,
+        Harmony::SplitComplementary,
+        Harmony::Analogous,
+    ];
+
+    /// lowercase name, as taken by FromStr
+    pub fn name(self) -> &'static str {
+        match self {
+            Harmony::Complementary => "complementary",
+            Harmony::Triadic => "triadic",
+            Harmony::SplitComplementary => "split-complementary",
+            Harmony::Analogous => "analogous",
+        }
+    }
+
+    /// the one after `current` in ALL, None after the last so that cycling passes by no harmony
+    pub fn cycle(current: Option<Harmony>) -> Option<Harmony> {
+        match current {
+            None => Some(Harmony::ALL[0]),
+            Some(h) => {
+                let i = Harmony::ALL.iter().position(|a| *a == h).unwrap();
+                Harmony::ALL.get(i + 1).copied()
+            }
+        }
+    }
+
+    /// how far the other hues are from the base one, in degrees
+    pub fn offsets(self) -> &'static [f32] {
+        match self {
+            Harmony::Complementary => &[180.],
+            Harmony::Triadic => &[120., 240.],
+            Harmony::SplitComplementary => &[150., 210.],
+            Harmony::Analogous => &[-30., 30.],
+        }
+    }
+
+    /// the other hues of the harmony, in degrees within [0,360)
+    pub fn hues(self, base: f32) -> Vec<f32> {
+        self.offsets()
+            .iter()
+            .map(|offset| (base + offset).rem_euclid(360.))
+            .collect()
+    }
+}
+
+/// Middle of the hue slice holding the most weight, from Distribution::histogram. None for spaces
+/// not sliced by hue, or without any chromatic color
+pub fn dominant_hue(histogram: &[(f32, f32)], space: Space) -> Option<f32> {
+    if !space.by_hue() {
+        return None;
+    }
+    histogram
+        .iter()
+        .filter(|(_, share)| *share > 0.)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(slice, _)| slice + space.binning().delta / 2.)
+}
+
+impl Display for Harmony {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Harmony {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Harmony::ALL
+            .into_iter()
+            .find(|h| h.name() == s)
+            .ok_or_else(|| Error::UnknownHarmony(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(Harmony::Complementary.offsets(), &[180.]);
+        assert_eq!(Harmony::Triadic.offsets(), &[120., 240.]);
+        assert_eq!(Harmony::SplitComplementary.offsets(), &[150., 210.]);
+        assert_eq!(Harmony::Analogous.offsets(), &[-30., 30.]);
+    }
+
+    #[test]
+    fn hues_wrap_around() {
+        assert_eq!(Harmony::Analogous.hues(10.), vec![340., 40.]);
+        assert_eq!(Harmony::Triadic.hues(300.), vec![60., 180.]);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for harmony in Harmony::ALL {
+            assert_eq!(harmony.name().parse::<Harmony>().unwrap(), harmony);
+        }
+        assert!(matches!(
+            "tetradic".parse::<Harmony>(),
+            Err(Error::UnknownHarmony(_))
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use sefenbu::{Harmony, Space};
+
+/// The harmony marked on the hue axis, and the hue it is built on
+#[derive(Resource, Clone, Copy, PartialEq, Default, Debug)]
+pub struct Harmonies {
+    pub harmony: Option<Harmony>,
+    /// build on the dominant hue of the image rather than the current slice
+    pub dominant: bool,
+}
+
+impl Harmonies {
+    /// the hue the harmony is built on, the current one until the dominant one is known
+    pub fn base(&self, current: f32, dominant: Option<f32>) -> f32 {
+        dominant.filter(|_| self.dominant).unwrap_or(current)
+    }
+
+    /// Every hue marked, in degrees: the other hues of the harmony, and its base when that isn't
+    /// the current slice, which is highlighted anyway
+    pub fn hues(&self, current: f32, dominant: Option<f32>) -> Vec<f32> {
+        let Some(harmony) = self.harmony else {
+            return vec![];
+        };
+        let base = self.base(current, dominant);
+        let mut hues = harmony.hues(base);
+        if base != current {
+            hues.push(base);
+        }
+        hues
+    }
+
+    /// hues as the filter shaders take them, unused ones negative, see shaders/harmony.wgsl
+    pub fn uniform(&self, current: f32, dominant: Option<f32>) -> Vec4 {
+        let mut uniform = [-1.; 4];
+        for (u, hue) in uniform.iter_mut().zip(self.hues(current, dominant)) {
+            *u = hue;
+        }
+        Vec4::from_array(uniform)
+    }
+
+    /// which hues are marked, shown over the image, empty without a harmony
+    pub fn note(&self, space: Space, current: f32, dominant: Option<f32>) -> String {
+        let Some(harmony) = self.harmony else {
+            return String::new();
+        };
+        if !space.by_hue() {
+            return format!("{} harmony: {} is not sliced by hue", harmony, space);
+        }
+        let base = self.base(current, dominant);
+        let hues: Vec<_> = harmony
+            .hues(base)
+            .iter()
+            .map(|hue| format!("{:.0}°", hue))
+            .collect();
+        let of = match dominant.filter(|_| self.dominant) {
+            Some(_) => "dominant hue ",
+            None => "",
+        };
+        format!("{} of {}{:.0}°: {}", harmony, of, base, hues.join(", "))
+    }
+}
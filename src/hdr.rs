@@ -0,0 +1,182 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use palette::{LinSrgb, Srgb};
+use sefenbu::{Profile, ToneMap};
+
+/// EV added or taken away per key press
+pub const EXPOSURE_STEP: f32 = 0.5;
+
+/// How 16-bit and floating-point images are brought into [0,1] before they are analyzed
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Exposure {
+    /// in stops, each one doubling the brightness
+    pub stops: f32,
+    pub tonemap: ToneMap,
+}
+
+/// The linear pixels of a 16-bit or floating-point image, kept to expose them again
+#[derive(Resource)]
+pub struct HdrSource {
+    /// row by row, alpha last
+    pixels: Vec<[f32; 4]>,
+    size: UVec2,
+    /// pixels with a channel above 1.0, before exposure
+    pub bright: usize,
+    /// which pixels were outside of sRGB, when the image was converted from a color profile
+    pub outside: Option<Vec<bool>>,
+    /// the texture shown in the image view
+    pub texture: Handle<Image>,
+}
+
+/// how a format bevy loads 16-bit and floating-point images as stores colors
+enum Encoding {
+    /// 16-bit integers with the transfer curve of the image applied
+    Gamma,
+    /// linear floats, possibly above 1.0
+    Linear,
+}
+
+fn encoding(format: TextureFormat) -> Option<Encoding> {
+    match format {
+        TextureFormat::Rgba16Unorm
+        | TextureFormat::Rgba16Uint
+        | TextureFormat::Rg16Unorm
+        | TextureFormat::Rg16Uint
+        | TextureFormat::R16Unorm
+        | TextureFormat::R16Uint => Some(Encoding::Gamma),
+        TextureFormat::Rgba16Float | TextureFormat::Rgba32Float => Some(Encoding::Linear),
+        _ => None,
+    }
+}
+
+/// A pixel at full precision in linear sRGB, alpha last, and whether it was outside of sRGB
+fn linear_at(
+    image: &Image,
+    encoding: &Encoding,
+    i: u32,
+    j: u32,
+    profile: Option<&Profile>,
+) -> Option<([f32; 4], bool)> {
+    let c = image.get_color_at(i, j).ok()?.to_linear();
+    // grayscale, with alpha in the second channel
+    let [r, g, b, a] = match image.texture_descriptor.format {
+        TextureFormat::R16Unorm | TextureFormat::R16Uint => [c.red, c.red, c.red, 1.],
+        TextureFormat::Rg16Unorm | TextureFormat::Rg16Uint => [c.red, c.red, c.red, c.green],
+        _ => [c.red, c.green, c.blue, c.alpha],
+    };
+    let (rgb, outside) = match encoding {
+        // integers are as encoded in the file, bevy takes them as linear
+        Encoding::Gamma => {
+            let (srgb, outside) = match profile {
+                Some(profile) => profile.to_srgb(Srgb::new(r, g, b)),
+                None => (Srgb::new(r, g, b), false),
+            };
+            (srgb.into_linear(), outside)
+        }
+        // only the PNG, JPEG and WebP profiles are read, so floats are sRGB
+        Encoding::Linear => (LinSrgb::new(r, g, b), false),
+    };
+    Some(([rgb.red, rgb.green, rgb.blue, a], outside))
+}
+
+/// A pixel as the analysis takes it, and its alpha, without converting the whole image
+pub fn color_at(
+    image: &Image,
+    i: u32,
+    j: u32,
+    profile: Option<&Profile>,
+    exposure: Exposure,
+) -> Option<(Srgb, f32)> {
+    match encoding(image.texture_descriptor.format) {
+        Some(encoding) => {
+            let ([r, g, b, a], _) = linear_at(image, &encoding, i, j, profile)?;
+            let rgb = exposure.tonemap.apply(LinSrgb::new(r, g, b), exposure.stops);
+            Some((Srgb::from_linear(rgb), a))
+        }
+        None => {
+            let c: Srgba = image.get_color_at(i, j).ok()?.into();
+            let rgb = Srgb::new(c.red, c.green, c.blue);
+            Some((profile.map_or(rgb, |p| p.to_srgb(rgb).0), c.alpha))
+        }
+    }
+}
+
+impl HdrSource {
+    /// Read the pixels at full precision, converting them from the profile the image declares.
+    /// None for 8-bit images, which are analyzed as bevy loads them
+    pub fn new(texture: Handle<Image>, image: &Image, profile: Option<&Profile>) -> Option<Self> {
+        let encoding = encoding(image.texture_descriptor.format)?;
+        let size = image.size();
+        let mut pixels = Vec::with_capacity(size.x as usize * size.y as usize);
+        let mut outside = Vec::with_capacity(pixels.capacity());
+        for j in 0..size.y {
+            for i in 0..size.x {
+                let (pixel, out) = linear_at(image, &encoding, i, j, profile)?;
+                pixels.push(pixel);
+                outside.push(out);
+            }
+        }
+        let bright = pixels
+            .iter()
+            .filter(|[r, g, b, _]| *r > 1. || *g > 1. || *b > 1.)
+            .count();
+        Some(HdrSource {
+            pixels,
+            size,
+            bright,
+            outside: (profile.is_some() && matches!(encoding, Encoding::Gamma)).then_some(outside),
+            texture,
+        })
+    }
+
+    /// The pixels after exposure and tone mapping, as linear floats for the analysis, and as 8-bit
+    /// sRGB for the image view
+    pub fn expose(&self, exposure: Exposure) -> (Image, Image) {
+        let mut analyzed = Vec::with_capacity(self.pixels.len() * 16);
+        let mut shown = Vec::with_capacity(self.pixels.len() * 4);
+        for [r, g, b, a] in &self.pixels {
+            let rgb = exposure
+                .tonemap
+                .apply(LinSrgb::new(*r, *g, *b), exposure.stops);
+            let a = a.clamp(0., 1.);
+            for c in [rgb.red, rgb.green, rgb.blue, a] {
+                analyzed.extend_from_slice(&c.to_le_bytes());
+            }
+            let srgb: Srgb<u8> = Srgb::from_linear(rgb);
+            shown.extend_from_slice(&[srgb.red, srgb.green, srgb.blue, (a * 255.).round() as u8]);
+        }
+        (
+            self.image(analyzed, TextureFormat::Rgba32Float),
+            self.image(shown, TextureFormat::Rgba8UnormSrgb),
+        )
+    }
+
+    /// how many pixels are too bright and how they are brought down, shown over the image
+    pub fn note(&self, exposure: Exposure) -> String {
+        let share = self.bright as f32 / self.pixels.len().max(1) as f32;
+        format!(
+            "{} pixels ({:.2}%) above 1.0, exposure {:+.1} EV, {} tone mapping",
+            self.bright,
+            share * 100.,
+            exposure.stops,
+            exposure.tonemap
+        )
+    }
+
+    fn image(&self, data: Vec<u8>, format: TextureFormat) -> Image {
+        Image::new(
+            Extent3d {
+                width: self.size.x,
+                height: self.size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+            RenderAssetUsages::default(),
+        )
+    }
+}
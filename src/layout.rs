@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+use crate::scene::CamViewPort;
+
+const IMG_VIEW_W_RATIO: f32 = 0.8;
+const VIZ3D_H_RATIO: f32 = 0.5;
+// keep every panel grabbable
+const RATIO_MIN: f32 = 0.1;
+const RATIO_MAX: f32 = 0.9;
+/// distance in logical pixels at which a splitter can be grabbed
+const SPLITTER_GRAB: f32 = 6.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Splitter {
+    /// between the image and the viz column
+    Vertical,
+    /// between the 3d and 2d viz
+    Horizontal,
+}
+
+/// Where each panel goes, read by both the viewports and input hit-testing
+#[derive(Resource)]
+pub struct Layout {
+    /// width of the image view relative to the window
+    pub img_w_ratio: f32,
+    /// height of the 3d viz relative to the window
+    pub viz3d_h_ratio: f32,
+    /// put the image on the right
+    pub mirrored: bool,
+    /// the only panel shown, if any
+    pub maximized: Option<CamViewPort>,
+    /// splitter currently being dragged
+    pub dragging: Option<Splitter>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            img_w_ratio: IMG_VIEW_W_RATIO,
+            viz3d_h_ratio: VIZ3D_H_RATIO,
+            mirrored: false,
+            maximized: None,
+            dragging: None,
+        }
+    }
+}
+
+impl Layout {
+    /// x of the splitter between image and viz, normalized
+    fn split_x(&self) -> f32 {
+        if self.mirrored {
+            1. - self.img_w_ratio
+        } else {
+            self.img_w_ratio
+        }
+    }
+
+    /// normalized rect of a panel with the origin at the top left, None if hidden
+    pub fn rect(&self, view: CamViewPort) -> Option<Rect> {
+        if let Some(m) = self.maximized {
+            return (m == view).then_some(Rect::new(0., 0., 1., 1.));
+        }
+
+        let split_x = self.split_x();
+        let (img_x, viz_x) = if self.mirrored {
+            (split_x, 0.)
+        } else {
+            (0., split_x)
+        };
+        let viz_w = 1. - self.img_w_ratio;
+        Some(match view {
+            CamViewPort::ImageFilter => Rect::new(img_x, 0., img_x + self.img_w_ratio, 1.),
+            CamViewPort::Viz3d => Rect::new(viz_x, 0., viz_x + viz_w, self.viz3d_h_ratio),
+            CamViewPort::Viz2d => Rect::new(viz_x, self.viz3d_h_ratio, viz_x + viz_w, 1.),
+        })
+    }
+
+    /// panel under a normalized position
+    pub fn hit(&self, pos: Vec2) -> Option<CamViewPort> {
+        [
+            CamViewPort::ImageFilter,
+            CamViewPort::Viz2d,
+            CamViewPort::Viz3d,
+        ]
+        .into_iter()
+        .find(|v| self.rect(*v).is_some_and(|r| r.contains(pos)))
+    }
+
+    /// splitter under a position in logical pixels
+    pub fn splitter_at(&self, pos: Vec2, size: Vec2) -> Option<Splitter> {
+        if self.maximized.is_some() {
+            return None;
+        }
+
+        if (pos.x - self.split_x() * size.x).abs() < SPLITTER_GRAB {
+            return Some(Splitter::Vertical);
+        }
+        let viz = self.rect(CamViewPort::Viz3d)?;
+        if pos.x > viz.min.x * size.x
+            && pos.x < viz.max.x * size.x
+            && (pos.y - self.viz3d_h_ratio * size.y).abs() < SPLITTER_GRAB
+        {
+            return Some(Splitter::Horizontal);
+        }
+        None
+    }
+
+    /// move the dragged splitter to a normalized position
+    pub fn drag_to(&mut self, pos: Vec2) {
+        match self.dragging {
+            Some(Splitter::Vertical) => {
+                let x = if self.mirrored { 1. - pos.x } else { pos.x };
+                self.img_w_ratio = x.clamp(RATIO_MIN, RATIO_MAX);
+            }
+            Some(Splitter::Horizontal) => {
+                self.viz3d_h_ratio = pos.y.clamp(RATIO_MIN, RATIO_MAX);
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Color distribution analysis behind sefenbu, without the viewer. Build with
+//! `default-features = false` to only depend on palette.
+//!
+//! ```no_run
+//! use sefenbu::{Distribution, Space};
+//!
+//! # let (width, height, pixels) = (1, 1, vec![255u8, 0, 0, 255]);
+//! let distribution = Distribution::from_rgba8(width, height, &pixels, Space::Okhsv)?;
+//! for (hue, share) in distribution.histogram() {
+//!     println!("{hue}: {share}");
+//! }
+//! # Ok::<(), sefenbu::Error>(())
+//! ```
+
+pub mod contrast;
+pub mod cvd;
+pub mod distribution;
+pub mod harmony;
+pub mod profile;
+pub mod quantize;
+pub mod scheme;
+pub mod space;
+pub mod tonemap;
+
+use std::fmt::Display;
+
+pub use cvd::Deficiency;
+pub use distribution::Distribution;
+pub use harmony::Harmony;
+pub use profile::Profile;
+pub use quantize::Metric;
+pub use scheme::Scheme;
+pub use space::Space;
+pub use tonemap::ToneMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// the pixel buffer doesn't hold width * height RGBA pixels
+    SizeMismatch { expected: usize, actual: usize },
+    /// the name isn't one of Space::ALL
+    UnknownSpace(String),
+    /// a color profile that can't be converted from, and why
+    UnsupportedProfile(String),
+    /// the name isn't one of ToneMap::ALL
+    UnknownToneMap(String),
+    /// the name isn't one of Deficiency::ALL
+    UnknownDeficiency(String),
+    /// the name isn't one of Harmony::ALL
+    UnknownHarmony(String),
+    /// the name isn't one of Metric::ALL
+    UnknownMetric(String),
+    /// no #rrggbb color in a palette
+    EmptyPalette,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::SizeMismatch { expected, actual } => {
+                write!(f, "Expected {} bytes of pixels, got {}", expected, actual)
+            }
+            Error::UnknownSpace(s) => {
+                let names: Vec<_> = Space::ALL.iter().map(|space| space.name()).collect();
+                write!(
+                    f,
+                    "Did not recognize color space '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+            Error::UnsupportedProfile(why) => write!(f, "Unsupported color profile: {}", why),
+            Error::UnknownToneMap(s) => {
+                let names: Vec<_> = ToneMap::ALL.iter().map(|t| t.name()).collect();
+                write!(
+                    f,
+                    "Did not recognize tone mapping '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+            Error::UnknownDeficiency(s) => {
+                let names: Vec<_> = Deficiency::ALL.iter().map(|d| d.name()).collect();
+                write!(
+                    f,
+                    "Did not recognize color vision deficiency '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+            Error::UnknownHarmony(s) => {
+                let names: Vec<_> = Harmony::ALL.iter().map(|h| h.name()).collect();
+                write!(
+                    f,
+                    "Did not recognize harmony '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+            Error::UnknownMetric(s) => {
+                let names: Vec<_> = Metric::ALL.iter().map(|m| m.name()).collect();
+                write!(
+                    f,
+                    "Did not recognize color difference '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+            Error::EmptyPalette => write!(f, "No #rrggbb color in the palette"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,72 +1,363 @@
+// bevy systems take their dependencies as arguments
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+mod bindings;
 mod cli;
+mod config;
 mod controls;
+mod decoded;
+mod formats;
+mod frames;
+mod harmony_overlay;
+mod hdr;
+mod layout;
+mod metadata;
 mod providers;
+mod quantize_view;
+mod scaling;
 mod scene;
+mod selection;
+mod shaders;
+mod simulation;
+mod swatches;
+mod terminal;
+mod weights;
+
+use std::{fmt::Display, path::Path};
 
 use bevy::{prelude::*, sprite::Material2dPlugin};
 use clap::Parser;
 use cli::Cli;
+use config::Config;
 use controls::*;
+use decoded::Decoded;
+use frames::Frames;
+use harmony_overlay::Harmonies;
+use hdr::Exposure;
+use layout::Layout;
+use metadata::SourceProfile;
 use providers::{
     generic::CSpaceProvider, hsl::HslProvider, hsv::HsvProvider, okhsl::OkhslProvider,
     okhsv::OkhsvProvider,
 };
+use quantize_view::Quantization;
+use scaling::Scaling;
 use scene::*;
+use sefenbu::{quantize::parse_palette, Space};
+use selection::{Roi, Selection};
+use simulation::Simulation;
+use swatches::Swatches;
+use terminal::TerminalScheme;
 
 fn main() {
     let args = Cli::parse();
-    match args.using.clone().unwrap_or("okhsv".to_string()).as_str() {
-        "okhsv" => app_run::<OkhsvProvider>(args),
-        "okhsl" => app_run::<OkhslProvider>(args),
-        "hsv" => app_run::<HsvProvider>(args),
-        "hsl" => app_run::<HslProvider>(args),
-        s => {
-            println!("Did not recognize color space '{}'", s);
-            std::process::exit(-1);
+    // everything that can be checked up front, so that no window opens just to sit empty
+    let mut frames = None;
+    let mut decoded = None;
+    // frames of a directory and clipboard images are taken as sRGB
+    let mut profile = Ok(None);
+    match args.file.as_deref() {
+        None => {
+            let image = decoded::read_clipboard().unwrap_or_else(|e| exit_with_error(e));
+            decoded = Some(Decoded(image));
+        }
+        Some(decoded::STDIN) => {
+            let bytes = decoded::read_stdin().unwrap_or_else(|e| exit_with_error(e));
+            let image = decoded::decode(&bytes).unwrap_or_else(|e| exit_with_error(e));
+            decoded = Some(Decoded(image));
+            profile = metadata::parse_profile(&bytes);
+        }
+        Some(dir) if Path::new(dir).is_dir() => {
+            frames = Some(frames::read_sequence(dir).unwrap_or_else(|e| exit_with_error(e)));
+        }
+        Some(file) => {
+            let animation = check_image(file).and_then(|_| frames::read_animation(file));
+            frames = animation.unwrap_or_else(|e| exit_with_error(e));
+            profile = metadata::read_profile(file);
+        }
+    }
+    if let Some(mask) = &args.mask {
+        // the size of a file read as a single image is looked up without decoding it
+        let size = match (&frames, &decoded, args.file.as_deref()) {
+            (Some(frames), _, _) => Some(frames.images[0].size()),
+            (_, Some(decoded), _) => Some(decoded.0.size()),
+            (_, _, Some(file)) => image_size(file),
+            _ => None,
+        };
+        if let Err(e) = check_image(mask).and_then(|_| check_mask_size(mask, size)) {
+            exit_with_error(e);
+        }
+    }
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(c) => c,
+        Err(e) => exit_with_error(e),
+    };
+    // analysis happens in sRGB, anything else is converted once loaded
+    let source = if args.ignore_profile {
+        None
+    } else {
+        match profile {
+            Ok(source) => source.filter(|s| !s.profile.is_srgb()),
+            Err(e) => {
+                eprintln!("warning: {}, taking the image as sRGB", e);
+                None
+            }
+        }
+    };
+    match args.using {
+        Space::Okhsv => app_run::<OkhsvProvider>(args, config, source, frames, decoded),
+        Space::Okhsl => app_run::<OkhslProvider>(args, config, source, frames, decoded),
+        Space::Hsv => app_run::<HsvProvider>(args, config, source, frames, decoded),
+        Space::Hsl => app_run::<HslProvider>(args, config, source, frames, decoded),
+    }
+}
+
+/// report a problem sefenbu can't go on with and quit
+pub fn exit_with_error(msg: impl Display) -> ! {
+    eprintln!("error: {}", msg);
+    std::process::exit(1);
+}
+
+/// the file exists, can be read and has an extension one of the image loaders takes
+fn check_image(file: &str) -> Result<(), String> {
+    let path = Path::new(file);
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Cannot read image '{}': {}", file, e))?;
+    if !metadata.is_file() {
+        return Err(format!("Cannot read image '{}': not a file", file));
+    }
+    std::fs::File::open(path).map_err(|e| format!("Cannot read image '{}': {}", file, e))?;
+
+    let supported = formats::supported_extensions();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if supported.contains(&ext) => Ok(()),
+        Some("avif") => Err(format!(
+            "Cannot open '{}', AVIF needs sefenbu built with --features avif",
+            file
+        )),
+        _ => Err(format!(
+            "Unsupported image format '{}', expected one of {}",
+            file,
+            supported.join(", ")
+        )),
+    }
+}
+
+/// width and height of an image file, None for formats only bevy reads
+fn image_size(file: &str) -> Option<UVec2> {
+    let (width, height) = image::image_dimensions(file).ok()?;
+    Some(UVec2::new(width, height))
+}
+
+/// the mask has the size of the image, when both are known before loading them. draw_scene checks
+/// the others once loaded
+fn check_mask_size(mask: &str, image: Option<UVec2>) -> Result<(), String> {
+    let (Some(image), Some(size)) = (image, image_size(mask)) else {
+        return Ok(());
+    };
+    if size != image {
+        return Err(format!(
+            "mask is {}x{} but the image is {}x{}",
+            size.x, size.y, image.x, image.y
+        ));
+    }
+    Ok(())
+}
+
+fn primary_window(args: &Cli) -> Window {
+    let monitor = args
+        .monitor
+        .map(MonitorSelection::Index)
+        .unwrap_or(MonitorSelection::Primary);
+    let mut window = Window {
+        title: "sefenbu".to_string(),
+        mode: bevy::window::WindowMode::BorderlessFullscreen(monitor),
+        present_mode: bevy::window::PresentMode::AutoNoVsync,
+        ..default()
+    };
+
+    if args.windowed || args.size.is_some() {
+        window.mode = bevy::window::WindowMode::Windowed;
+        window.position = WindowPosition::Centered(monitor);
+        if let Some(size) = args.size {
+            window.resolution = (size.width, size.height).into();
         }
     }
+    window
 }
 
-fn app_run<A: CSpaceProvider>(args: Cli)
-where
+fn app_run<A: CSpaceProvider>(
+    args: Cli,
+    config: Config,
+    source: Option<SourceProfile>,
+    frames: Option<Frames>,
+    decoded: Option<Decoded>,
+) where
     Material2dPlugin<A::FilterMaterial>: Plugin,
     Material2dPlugin<A::Viz2dMaterial>: Plugin,
     MaterialPlugin<A::Viz3dMaterial>: Plugin,
 {
-    let mut exe_path = std::env::current_exe().unwrap();
-    if !(exe_path.pop()) {
-        println!("Cannot pop exe path: {}", exe_path.to_string_lossy());
-        std::process::exit(-1);
-    }
+    // shaders are built in, so paths given on the command line are relative to where we are
+    let cwd = match std::env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(e) => exit_with_error(format!("Cannot get working directory: {}", e)),
+    };
+    let shader_dir = args.shader_dir.as_ref().map(|dir| cwd.join(dir));
 
     let default_plugin = DefaultPlugins
         .set(AssetPlugin {
-            file_path: exe_path.to_string_lossy().into_owned(),
+            file_path: cwd.to_string_lossy().into_owned(),
             ..Default::default()
         })
         .set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "sefenbu".to_string(),
-                mode: bevy::window::WindowMode::BorderlessFullscreen(MonitorSelection::Primary),
-                present_mode: bevy::window::PresentMode::AutoNoVsync,
-                ..default()
-            }),
+            primary_window: Some(primary_window(&args)),
             ..default()
         })
         .set(bevy::log::LogPlugin::default());
 
-    App::new()
-        .insert_resource(args)
+    let exposure = Exposure {
+        stops: args.exposure,
+        tonemap: args.tonemap,
+    };
+    let simulation = Simulation {
+        cvd: args.cvd,
+        analysis: args.cvd_analysis,
+    };
+    let harmonies = Harmonies {
+        harmony: args.harmony,
+        dominant: args.harmony_dominant,
+    };
+    let palette = args.palette.as_deref().map(|file| {
+        let text = std::fs::read_to_string(file).map_err(|e| e.to_string());
+        match text.and_then(|text| parse_palette(&text).map_err(|e| e.to_string())) {
+            Ok(palette) => palette,
+            Err(e) => exit_with_error(format!("Cannot read palette '{}': {}", file, e)),
+        }
+    });
+    let quantization = Quantization {
+        view: None,
+        metric: args.delta_e,
+        palette,
+    };
+    let scaling = Scaling {
+        mode: args.scale,
+        normalization: args.normalize,
+        percentile: args.percentile,
+    };
+
+    let mut app = App::new();
+    app.register_asset_source(shaders::SOURCE, shaders::source(shader_dir.as_deref()));
+    if let Some(source) = source {
+        app.insert_resource(source);
+    }
+    if let Some(frames) = frames {
+        app.insert_resource(frames);
+    }
+    if let Some(decoded) = decoded {
+        app.insert_resource(decoded);
+    }
+    app.insert_resource(args)
+        .insert_resource(scaling)
+        .insert_resource(exposure)
+        .insert_resource(simulation)
+        .insert_resource(harmonies)
+        .insert_resource(quantization)
+        .insert_resource(config.bindings)
+        .init_resource::<Layout>()
+        .init_resource::<Selection>()
+        .init_resource::<Roi>()
+        .init_resource::<Swatches>()
+        .init_resource::<TerminalScheme>()
         .add_plugins((
             default_plugin,
             Material2dPlugin::<A::FilterMaterial>::default(),
             Material2dPlugin::<A::Viz2dMaterial>::default(),
             MaterialPlugin::<A::Viz3dMaterial>::default(),
-        ))
-        .add_systems(Startup, setup_scene_pre::<A>)
+        ));
+    #[cfg(feature = "avif")]
+    app.init_asset_loader::<formats::avif::AvifLoader>();
+    app.add_systems(Startup, setup_scene_pre::<A>)
         .add_systems(Update, draw_scene::<A>)
-        .add_systems(Update, control_blob)
-        .add_systems(Update, change_param::<A>)
+        .add_systems(Update, drag_splitters)
+        .add_systems(Update, arrange_panels)
+        .add_systems(Update, control_blob.after(drag_splitters))
+        .add_systems(Update, control_image_view.after(drag_splitters))
+        .add_systems(Update, change_param::<A>.after(drag_splitters))
+        .add_systems(Update, change_scaling)
+        .add_systems(Update, control_selection.after(drag_splitters))
+        .add_systems(Update, draw_selection.after(control_selection))
+        .add_systems(Update, pick_swatch.after(drag_splitters))
+        .add_systems(Update, draw_contrast.after(pick_swatch))
+        .add_systems(Update, change_exposure)
+        .add_systems(
+            Update,
+            apply_exposure::<A>
+                .after(change_exposure)
+                .before(refine_histogram::<A>),
+        )
+        .add_systems(Update, change_simulation)
+        .add_systems(
+            Update,
+            apply_simulation::<A>
+                .after(change_simulation)
+                .after(draw_scene::<A>)
+                .before(refine_histogram::<A>),
+        )
+        .add_systems(Update, change_harmonies)
+        .add_systems(
+            Update,
+            apply_harmonies::<A>
+                .after(change_harmonies)
+                .after(change_param::<A>)
+                .after(refine_histogram::<A>)
+                .before(update_viz::<A>),
+        )
+        .add_systems(Update, draw_harmonies::<A>.after(apply_harmonies::<A>))
+        .add_systems(Update, change_scheme)
+        .add_systems(
+            Update,
+            make_scheme
+                .after(change_scheme)
+                .after(refine_histogram::<A>),
+        )
+        .add_systems(Update, draw_scheme.after(make_scheme))
+        .add_systems(Update, change_quantization)
+        .add_systems(
+            Update,
+            apply_quantization
+                .after(change_quantization)
+                .after(pick_swatch)
+                .after(refine_histogram::<A>),
+        )
+        .add_systems(Update, paste_image)
+        .add_systems(
+            Update,
+            replace_image::<A>
+                .after(paste_image)
+                .before(refine_histogram::<A>),
+        )
+        .add_systems(Update, change_frame)
+        .add_systems(
+            Update,
+            show_frame::<A>
+                .after(change_frame)
+                .before(refine_histogram::<A>),
+        )
+        .add_systems(Update, draw_timeline::<A>.after(change_frame))
+        .add_systems(
+            Update,
+            refine_histogram::<A>
+                .after(control_selection)
+                .before(update_viz::<A>),
+        )
+        .add_systems(
+            Update,
+            update_viz::<A>
+                .after(change_param::<A>)
+                .after(change_scaling),
+        )
         .add_systems(Update, set_viewports)
+        .add_systems(Update, toggle_fullscreen)
+        .add_systems(Update, toggle_gamut)
         .run();
 }
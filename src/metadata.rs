@@ -0,0 +1,194 @@
+use bevy::prelude::Resource;
+use sefenbu::{Profile, profile::icc_description};
+
+/// The color space the image declares, when it isn't sRGB
+#[derive(Resource)]
+pub struct SourceProfile {
+    pub profile: Profile,
+    /// where it came from, e.g. "ICC profile 'Display P3'"
+    pub label: String,
+}
+
+/// Color metadata found in a file, before deciding which one counts
+#[derive(Default)]
+struct Embedded {
+    /// colour primaries and transfer characteristics
+    cicp: Option<(u8, u8)>,
+    icc: Option<Vec<u8>>,
+    /// TIFF structured EXIF
+    exif: Option<Vec<u8>>,
+}
+
+/// Look for the color space of a PNG, JPEG or WebP file, see parse_profile
+pub fn read_profile(file: &str) -> Result<Option<SourceProfile>, String> {
+    let data = std::fs::read(file).map_err(|e| format!("Cannot read image '{}': {}", file, e))?;
+    parse_profile(&data)
+}
+
+/// Look for the color space of a PNG, JPEG or WebP image, in order of precedence: cICP, an ICC
+/// profile, then the EXIF color space. None if the image has none, so it is taken as sRGB
+pub fn parse_profile(data: &[u8]) -> Result<Option<SourceProfile>, String> {
+    let embedded = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png(data)
+    } else if data.starts_with(&[0xff, 0xd8]) {
+        jpeg(data)
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        webp(data)
+    } else {
+        Embedded::default()
+    };
+
+    if let Some((primaries, transfer)) = embedded.cicp {
+        let profile = Profile::from_cicp(primaries, transfer).map_err(|e| e.to_string())?;
+        let label = format!("cICP primaries {}, transfer {}", primaries, transfer);
+        return Ok(Some(SourceProfile { profile, label }));
+    }
+    if let Some(icc) = embedded.icc {
+        let profile = Profile::from_icc(&icc).map_err(|e| e.to_string())?;
+        let label = match icc_description(&icc) {
+            Some(name) => format!("ICC profile '{}'", name),
+            None => "ICC profile".to_string(),
+        };
+        return Ok(Some(SourceProfile { profile, label }));
+    }
+    Ok(embedded
+        .exif
+        .and_then(|exif| exif_profile(&exif))
+        .map(|(profile, name)| SourceProfile {
+            profile,
+            label: format!("EXIF {}", name),
+        }))
+}
+
+fn png(data: &[u8]) -> Embedded {
+    let mut found = Embedded::default();
+    let mut at = 8;
+    while let Some(len) = png_chunk_len(data, at) {
+        let kind = &data[at + 4..at + 8];
+        let Some(body) = data.get(at + 8..at + 8 + len as usize) else {
+            break;
+        };
+        match kind {
+            b"cICP" if body.len() >= 2 => found.cicp = Some((body[0], body[1])),
+            // keyword, then the compression method before the zlib stream
+            b"iCCP" => {
+                found.icc = body
+                    .iter()
+                    .position(|b| *b == 0)
+                    .and_then(|nul| body.get(nul + 2..))
+                    .and_then(|z| miniz_oxide::inflate::decompress_to_vec_zlib(z).ok())
+            }
+            b"eXIf" => found.exif = Some(body.to_vec()),
+            b"IEND" => break,
+            _ => {}
+        }
+        // length, type and crc
+        at += 12 + len as usize;
+    }
+    found
+}
+
+fn jpeg(data: &[u8]) -> Embedded {
+    let mut found = Embedded::default();
+    // profiles too large for one segment are split, each part numbered
+    let mut icc_parts: Vec<(u8, &[u8])> = vec![];
+    let mut at = 2;
+    while at + 4 <= data.len() && data[at] == 0xff {
+        let marker = data[at + 1];
+        // the image data starts, no more metadata
+        if marker == 0xda || marker == 0xd9 {
+            break;
+        }
+        let len = u16::from_be_bytes([data[at + 2], data[at + 3]]) as usize;
+        let Some(body) = data.get(at + 4..at + 2 + len) else {
+            break;
+        };
+        match marker {
+            0xe2 if body.len() > 14 && body.starts_with(b"ICC_PROFILE\0") => {
+                icc_parts.push((body[12], &body[14..]))
+            }
+            0xe1 if body.starts_with(b"Exif\0\0") => found.exif = Some(body[6..].to_vec()),
+            _ => {}
+        }
+        at += 2 + len;
+    }
+    if !icc_parts.is_empty() {
+        icc_parts.sort_by_key(|(seq, _)| *seq);
+        found.icc = Some(
+            icc_parts
+                .iter()
+                .flat_map(|(_, p)| p.iter().copied())
+                .collect(),
+        );
+    }
+    found
+}
+
+fn webp(data: &[u8]) -> Embedded {
+    let mut found = Embedded::default();
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let kind = &data[at..at + 4];
+        let len = u32::from_le_bytes([data[at + 4], data[at + 5], data[at + 6], data[at + 7]]);
+        let Some(body) = data.get(at + 8..at + 8 + len as usize) else {
+            break;
+        };
+        match kind {
+            b"ICCP" => found.icc = Some(body.to_vec()),
+            // some writers keep the JPEG prefix
+            b"EXIF" => found.exif = Some(body.strip_prefix(b"Exif\0\0").unwrap_or(body).to_vec()),
+            _ => {}
+        }
+        // chunks are padded to even sizes
+        at += 8 + len as usize + len as usize % 2;
+    }
+    found
+}
+
+/// Adobe RGB when the EXIF says uncalibrated and the interoperability index is R03, the way
+/// cameras mark it. sRGB, or anything else, is left alone
+fn exif_profile(tiff: &[u8]) -> Option<(Profile, &'static str)> {
+    let little = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let b = tiff.get(at..at + 2)?;
+        Some(if little {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |at: usize| {
+        let b = tiff.get(at..at + 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(if little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+    // where the value of a tag is, inline as every tag read here fits in 4 bytes
+    let find = |ifd: usize, tag: u16| {
+        let count = u16_at(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|entry| u16_at(*entry) == Some(tag))
+            .map(|entry| entry + 8)
+    };
+
+    let ifd0 = u32_at(4)? as usize;
+    let exif_ifd = u32_at(find(ifd0, 0x8769)?)? as usize;
+    let uncalibrated = u16_at(find(exif_ifd, 0xa001)?)? == 0xffff;
+    let interop = u32_at(find(exif_ifd, 0xa005)?)? as usize;
+    let index = tiff.get(find(interop, 0x0001)?..)?.get(..3)?;
+    (uncalibrated && index == b"R03").then(|| (Profile::adobe_rgb(), "Adobe RGB"))
+}
+
+/// length of the chunk at, if its length and type are there
+fn png_chunk_len(data: &[u8], at: usize) -> Option<u32> {
+    let b = data.get(at..at + 8)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
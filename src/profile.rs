@@ -0,0 +1,490 @@
+use palette::{LinSrgb, Srgb};
+
+use crate::Error;
+
+type Matrix = [[f32; 3]; 3];
+
+/// how far outside of [0,1] linear sRGB a channel may be before the pixel counts as out of gamut,
+/// so that rounding doesn't flag colors on the edge
+const GAMUT_TOLERANCE: f32 = 0.5 / 255.;
+
+/// xy chromaticities of the red, green and blue primaries
+type Primaries = [[f32; 2]; 3];
+
+const SRGB: Primaries = [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06]];
+const DISPLAY_P3: Primaries = [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]];
+const ADOBE_RGB: Primaries = [[0.64, 0.33], [0.21, 0.71], [0.15, 0.06]];
+const BT2020: Primaries = [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]];
+
+/// chromaticities of D65, the white of sRGB
+const D65: [f32; 2] = [0.3127, 0.3290];
+
+/// adapts ICC's D50 connection space to D65
+const BRADFORD_D50_TO_D65: Matrix = [
+    [0.9555766, -0.0230393, 0.0631636],
+    [-0.0282895, 1.0099416, 0.0210077],
+    [0.0122982, -0.0204830, 1.3299098],
+];
+
+/// Maps an encoded channel value to linear light
+#[derive(Clone, Debug, PartialEq)]
+pub enum Curve {
+    /// ICC parametric curve, (a * x + b)^g + e from d on and c * x + f below
+    Parametric {
+        g: f32,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    },
+    /// sampled evenly over [0,1]
+    Table(Vec<f32>),
+}
+
+impl Curve {
+    pub const SRGB: Curve = Curve::Parametric {
+        g: 2.4,
+        a: 1. / 1.055,
+        b: 0.055 / 1.055,
+        c: 1. / 12.92,
+        d: 0.04045,
+        e: 0.,
+        f: 0.,
+    };
+
+    /// the inverse of the BT.709 and BT.2020 camera curve
+    pub const BT709: Curve = Curve::Parametric {
+        g: 1. / 0.45,
+        a: 1. / 1.099,
+        b: 0.099 / 1.099,
+        c: 1. / 4.5,
+        d: 0.081,
+        e: 0.,
+        f: 0.,
+    };
+
+    pub const fn gamma(g: f32) -> Curve {
+        Curve::Parametric {
+            g,
+            a: 1.,
+            b: 0.,
+            c: 0.,
+            d: 0.,
+            e: 0.,
+            f: 0.,
+        }
+    }
+
+    pub fn linearize(&self, v: f32) -> f32 {
+        match self {
+            Curve::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if v >= *d {
+                    (a * v + b).max(0.).powf(*g) + e
+                } else {
+                    c * v + f
+                }
+            }
+            Curve::Table(table) => {
+                let pos = v.clamp(0., 1.) * (table.len() - 1) as f32;
+                let i = (pos as usize).min(table.len() - 2);
+                let t = pos - i as f32;
+                table[i] * (1. - t) + table[i + 1] * t
+            }
+        }
+    }
+}
+
+/// How the RGB values of an image map to colors, so that they can be brought into sRGB, the
+/// space every analysis works in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// linear RGB to linear sRGB
+    matrix: Matrix,
+    curves: [Curve; 3],
+}
+
+impl Profile {
+    pub fn srgb() -> Self {
+        Profile::from_primaries(SRGB, D65, Curve::SRGB)
+    }
+
+    pub fn display_p3() -> Self {
+        Profile::from_primaries(DISPLAY_P3, D65, Curve::SRGB)
+    }
+
+    pub fn adobe_rgb() -> Self {
+        Profile::from_primaries(ADOBE_RGB, D65, Curve::gamma(563. / 256.))
+    }
+
+    pub fn bt2020() -> Self {
+        Profile::from_primaries(BT2020, D65, Curve::BT709)
+    }
+
+    /// RGB with the given xy chromaticities of red, green and blue, the same curve for every
+    /// channel. The white has to be D65, as no adaptation is done
+    pub fn from_primaries(primaries: [[f32; 2]; 3], white: [f32; 2], curve: Curve) -> Self {
+        let to_xyz = rgb_to_xyz(primaries, white);
+        Profile {
+            matrix: mul(&xyz_to_srgb(), &to_xyz),
+            curves: [curve.clone(), curve.clone(), curve],
+        }
+    }
+
+    /// Parse an ICC profile, only RGB matrix/TRC profiles are supported
+    pub fn from_icc(data: &[u8]) -> Result<Self, Error> {
+        let unsupported = |why: &str| Error::UnsupportedProfile(why.to_string());
+        if data.len() < 132 || &data[36..40] != b"acsp" {
+            return Err(unsupported("not an ICC profile"));
+        }
+        if &data[16..20] != b"RGB " {
+            return Err(unsupported("not an RGB profile"));
+        }
+        if &data[20..24] != b"XYZ " {
+            return Err(unsupported("only XYZ connection spaces are supported"));
+        }
+
+        let colorant = |sig: &[u8]| match icc_tag(data, sig) {
+            Some(t) if t.len() >= 20 && &t[..4] == b"XYZ " => {
+                Ok([s15_fixed16(t, 8), s15_fixed16(t, 12), s15_fixed16(t, 16)])
+            }
+            _ => Err(unsupported("only matrix/TRC profiles are supported")),
+        };
+        let curve = |sig: &[u8]| {
+            icc_tag(data, sig)
+                .and_then(parse_curve)
+                .ok_or_else(|| unsupported("missing or invalid tone curve"))
+        };
+
+        let (r, g, b) = (colorant(b"rXYZ")?, colorant(b"gXYZ")?, colorant(b"bXYZ")?);
+        let to_xyz = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        Ok(Profile {
+            matrix: mul(&xyz_to_srgb(), &mul(&BRADFORD_D50_TO_D65, &to_xyz)),
+            curves: [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?],
+        })
+    }
+
+    /// From the colour primaries and transfer characteristics codes of ITU-T H.273, as in the
+    /// cICP chunk of PNG
+    pub fn from_cicp(primaries: u8, transfer: u8) -> Result<Self, Error> {
+        let curve = match transfer {
+            1 | 6 | 14 | 15 => Curve::BT709,
+            4 => Curve::gamma(2.2),
+            5 => Curve::gamma(2.8),
+            8 => Curve::gamma(1.),
+            13 => Curve::SRGB,
+            16 | 18 => return Err(Error::UnsupportedProfile("HDR transfer".to_string())),
+            t => return Err(Error::UnsupportedProfile(format!("transfer {}", t))),
+        };
+        let primaries = match primaries {
+            1 => SRGB,
+            9 => BT2020,
+            12 => DISPLAY_P3,
+            p => return Err(Error::UnsupportedProfile(format!("primaries {}", p))),
+        };
+        Ok(Profile::from_primaries(primaries, D65, curve))
+    }
+
+    /// close enough to sRGB that converting would change nothing
+    pub fn is_srgb(&self) -> bool {
+        let identity = (0..3)
+            .all(|i| (0..3).all(|j| (self.matrix[i][j] - (i == j) as u8 as f32).abs() < 1e-3));
+        let mut samples = (0..=32).map(|i| i as f32 / 32.);
+        identity
+            && samples.all(|v| {
+                let expected = Curve::SRGB.linearize(v);
+                self.curves
+                    .iter()
+                    .all(|c| (c.linearize(v) - expected).abs() < 2e-3)
+            })
+    }
+
+    /// the color in sRGB, clipped, and whether it was outside of sRGB
+    pub fn to_srgb(&self, rgb: Srgb) -> (Srgb, bool) {
+        let linear = [
+            self.curves[0].linearize(rgb.red),
+            self.curves[1].linearize(rgb.green),
+            self.curves[2].linearize(rgb.blue),
+        ];
+        let (srgb, outside) = self.clip(linear);
+        (Srgb::from_linear(srgb), outside)
+    }
+
+    /// Bring 8-bit RGBA pixels into sRGB in place, alpha is kept. Returns whether each pixel was
+    /// outside of sRGB and got clipped
+    pub fn convert_rgba8(&self, pixels: &mut [u8]) -> Vec<bool> {
+        let luts: Vec<Vec<f32>> = self
+            .curves
+            .iter()
+            .map(|c| (0..=255).map(|v| c.linearize(v as f32 / 255.)).collect())
+            .collect();
+        pixels
+            .chunks_exact_mut(4)
+            .map(|p| {
+                let linear = [
+                    luts[0][p[0] as usize],
+                    luts[1][p[1] as usize],
+                    luts[2][p[2] as usize],
+                ];
+                let (srgb, outside) = self.clip(linear);
+                let srgb: Srgb<u8> = Srgb::from_linear(srgb);
+                p[..3].copy_from_slice(&[srgb.red, srgb.green, srgb.blue]);
+                outside
+            })
+            .collect()
+    }
+
+    fn clip(&self, linear: [f32; 3]) -> (LinSrgb, bool) {
+        let [r, g, b] = apply(&self.matrix, linear);
+        let range = -GAMUT_TOLERANCE..=1. + GAMUT_TOLERANCE;
+        let outside = [r, g, b].iter().any(|c| !range.contains(c));
+        (
+            LinSrgb::new(r.clamp(0., 1.), g.clamp(0., 1.), b.clamp(0., 1.)),
+            outside,
+        )
+    }
+}
+
+/// Name of an ICC profile, e.g. "Display P3"
+pub fn icc_description(data: &[u8]) -> Option<String> {
+    let t = icc_tag(data, b"desc")?;
+    match t.get(..4)? {
+        // ICC v2, ASCII
+        b"desc" => {
+            let len = be_u32(t, 8) as usize;
+            let text = t.get(12..12 + len)?;
+            let text = String::from_utf8_lossy(text);
+            Some(text.trim_end_matches('\0').to_string())
+        }
+        // ICC v4, the first of the localized UTF-16 strings
+        b"mluc" => {
+            let len = be_u32(t, 20) as usize;
+            let offset = be_u32(t, 24) as usize;
+            let units: Vec<u16> = t
+                .get(offset..offset + len)?
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+/// contents of a tag, going by the tag table after the header
+fn icc_tag<'a>(data: &'a [u8], sig: &[u8]) -> Option<&'a [u8]> {
+    let count = be_u32(data, 128) as usize;
+    (0..count)
+        .map(|i| 132 + i * 12)
+        .take_while(|entry| entry + 12 <= data.len())
+        .find(|entry| &data[*entry..entry + 4] == sig)
+        .and_then(|entry| {
+            let offset = be_u32(data, entry + 4) as usize;
+            let size = be_u32(data, entry + 8) as usize;
+            data.get(offset..offset.checked_add(size)?)
+        })
+}
+
+fn parse_curve(t: &[u8]) -> Option<Curve> {
+    match t.get(..4)? {
+        b"curv" => {
+            let count = be_u32(t, 8) as usize;
+            match count {
+                0 => Some(Curve::gamma(1.)),
+                1 => Some(Curve::gamma(be_u16(t, 12)? as f32 / 256.)),
+                _ => {
+                    let table: Option<Vec<f32>> = (0..count)
+                        .map(|i| Some(be_u16(t, 12 + i * 2)? as f32 / 65535.))
+                        .collect();
+                    table.map(Curve::Table)
+                }
+            }
+        }
+        b"para" => {
+            let kind = be_u16(t, 8)?;
+            let n = [1, 3, 4, 5, 7].get(kind as usize)?;
+            if t.len() < 12 + n * 4 {
+                return None;
+            }
+            let p: Vec<f32> = (0..*n).map(|i| s15_fixed16(t, 12 + i * 4)).collect();
+            // kinds 1 and 2 start the curve where a * x + b crosses 0, which a flat line never does
+            if (kind == 1 || kind == 2) && p[1] == 0. {
+                return None;
+            }
+            // every kind is a special case of the last one
+            let [g, a, b, c, d, e, f] = match kind {
+                0 => [p[0], 1., 0., 0., 0., 0., 0.],
+                1 => [p[0], p[1], p[2], 0., -p[2] / p[1], 0., 0.],
+                2 => [p[0], p[1], p[2], 0., -p[2] / p[1], p[3], p[3]],
+                3 => [p[0], p[1], p[2], p[3], p[4], 0., 0.],
+                _ => [p[0], p[1], p[2], p[3], p[4], p[5], p[6]],
+            };
+            Some(Curve::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn be_u32(data: &[u8], at: usize) -> u32 {
+    data.get(at..at + 4)
+        .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn s15_fixed16(data: &[u8], at: usize) -> f32 {
+    be_u32(data, at) as i32 as f32 / 65536.
+}
+
+/// linear RGB to XYZ, from the xy chromaticities of the primaries and the white
+fn rgb_to_xyz(primaries: Primaries, white: [f32; 2]) -> Matrix {
+    let xyz = |[x, y]: [f32; 2]| [x / y, 1., (1. - x - y) / y];
+    let [r, g, b] = primaries.map(xyz);
+    let primaries = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+    // scale the primaries so that they add up to the white
+    let s = apply(&inverse(&primaries), xyz(white));
+    primaries.map(|row| [row[0] * s[0], row[1] * s[1], row[2] * s[2]])
+}
+
+fn xyz_to_srgb() -> Matrix {
+    inverse(&rgb_to_xyz(SRGB, D65))
+}
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn apply(m: &Matrix, v: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn inverse(m: &Matrix) -> Matrix {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det: f32 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    let mut inv = [[0.; 3]; 3];
+    for (i, row) in inv.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            // transposed
+            *v = cofactor(j, i) / det;
+        }
+    }
+    inv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a para tag, the parameters written as s15Fixed16
+    fn para(kind: u16, params: &[f32]) -> Vec<u8> {
+        let mut t = b"para\0\0\0\0".to_vec();
+        t.extend(kind.to_be_bytes());
+        t.extend([0, 0]);
+        for p in params {
+            t.extend(((p * 65536.) as i32).to_be_bytes());
+        }
+        t
+    }
+
+    fn parametric([g, a, b, c, d, e, f]: [f32; 7]) -> Curve {
+        Curve::Parametric {
+            g,
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+        }
+    }
+
+    #[test]
+    fn srgb_from_cicp() {
+        assert!(Profile::from_cicp(1, 13).unwrap().is_srgb());
+        assert!(!Profile::from_cicp(12, 13).unwrap().is_srgb());
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let m = rgb_to_xyz(SRGB, D65);
+        // the luminance of the sRGB primaries
+        assert!((m[1][0] - 0.2126).abs() < 1e-3);
+        assert!((m[1][1] - 0.7152).abs() < 1e-3);
+        assert!((m[1][2] - 0.0722).abs() < 1e-3);
+        let identity = mul(&inverse(&m), &m);
+        for (i, row) in identity.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                assert!((v - (i == j) as u8 as f32).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn para_kinds() {
+        let cases = [
+            (0, vec![2.5], [2.5, 1., 0., 0., 0., 0., 0.]),
+            (1, vec![2., 0.5, -0.25], [2., 0.5, -0.25, 0., 0.5, 0., 0.]),
+            (
+                2,
+                vec![2., 0.5, -0.25, 0.125],
+                [2., 0.5, -0.25, 0., 0.5, 0.125, 0.125],
+            ),
+            (
+                3,
+                vec![2.5, 0.75, 0.25, 0.125, 0.0625],
+                [2.5, 0.75, 0.25, 0.125, 0.0625, 0., 0.],
+            ),
+            (
+                4,
+                vec![2.5, 0.75, 0.25, 0.125, 0.0625, 0.5, 0.25],
+                [2.5, 0.75, 0.25, 0.125, 0.0625, 0.5, 0.25],
+            ),
+        ];
+        for (kind, params, expected) in cases {
+            assert_eq!(
+                parse_curve(&para(kind, &params)),
+                Some(parametric(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn para_without_curve() {
+        // no slope, so no point the curve starts at
+        assert_eq!(parse_curve(&para(1, &[2., 0., 0.5])), None);
+        assert_eq!(parse_curve(&para(2, &[2., 0., 0.5, 0.])), None);
+        // unknown kind, and too few parameters
+        assert_eq!(parse_curve(&para(5, &[2.; 7])), None);
+        assert_eq!(parse_curve(&para(3, &[2.; 4])), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use bevy::{
     asset::RenderAssetUsages,
@@ -6,6 +6,16 @@ use bevy::{
     render::mesh::{Indices, PrimitiveTopology},
     sprite::Material2d,
 };
+use palette::Srgb;
+use sefenbu::{
+    Distribution,
+    space::{Bin, Space},
+};
+
+use crate::{harmony_overlay::Harmonies, simulation::Simulation, weights::PixelWeights};
+
+/// data point to weighted pixel count
+pub type SliceStats = BTreeMap<(i64, i64), f64>;
 
 pub trait CSpaceProvider: Provider + Resource + FromImage {
     type FilterMaterial: Material2d + FromImage;
@@ -21,17 +31,31 @@ pub trait FromImage {
     fn from_image(img: Handle<Image>) -> Self;
 }
 
+/// Color of a pixel as the analysis takes it, alpha is handled by the weights. Pixels bevy can't
+/// read back have no weight and draw_scene turns such images down, read them as black anyway
+pub fn srgb_at(img: &Image, i: u32, j: u32) -> Srgb {
+    match img.get_color_at(i, j) {
+        Ok(c) => {
+            let c: Srgba = c.into();
+            Srgb::new(c.red, c.green, c.blue)
+        }
+        Err(_) => Srgb::new(0., 0., 0.),
+    }
+}
+
 pub trait Provider {
+    const SPACE: Space;
+
     // for params
-    const MAX: f32;
-    const MIN: f32;
-    const DELTA: f32;
+    const MAX: f32 = Self::SPACE.binning().max;
+    const MIN: f32 = Self::SPACE.binning().min;
+    const DELTA: f32 = Self::SPACE.binning().delta;
 
     // for other 2 params
-    const X_MAX: f32;
-    const X_DELTA: f32;
-    const Z_MAX: f32;
-    const Z_DELTA: f32;
+    const X_MAX: f32 = Self::SPACE.binning().x_max;
+    const X_DELTA: f32 = Self::SPACE.binning().x_delta;
+    const Z_MAX: f32 = Self::SPACE.binning().z_max;
+    const Z_DELTA: f32 = Self::SPACE.binning().z_delta;
 
     // length of rect
     const DX: f32 = Self::X_DELTA * Self::X_DELTA / Self::X_MAX;
@@ -52,28 +76,60 @@ pub trait Provider {
 
     /// return data point and if it's relevant wrt current param
     fn inspect(&mut self, img: &Image, i: u32, j: u32) -> (bool, (i64, i64));
-
-    /// draw 3d viz mesh
-    fn create_mesh(&mut self, img: &Image) -> Mesh {
-        // collect distribution
-        let mut stats: BTreeMap<(i64, i64), i64> = BTreeMap::new();
-        let w = img.width();
-        let h = img.height();
+    /// return the slice the pixel falls in and its data point, None if left out of all slices
+    fn locate(&mut self, img: &Image, i: u32, j: u32) -> Option<Bin>;
+
+    /// chroma below which pixels have no meaningful param, and whether to keep them in the
+    /// slices anyway. Only matters when slicing by hue
+    fn set_achromatic(&mut self, _threshold: f32, _keep: bool) {}
+    /// chroma below which pixels go in the achromatic bucket of the histogram
+    fn achromatic(&self) -> f32 {
+        0.
+    }
+    /// show pixels left out by the mask as outside of every slice
+    fn set_mask(&mut self, mask: Handle<Image>, invert: bool);
+    /// show the image as seen with a color vision deficiency, and slice it that way when the
+    /// deficiency is analyzed too
+    fn set_simulation(&mut self, simulation: Simulation);
+    /// mark the hues in harmony with the current one, or with the dominant one of the image when
+    /// known. Only matters when slicing by hue
+    fn set_harmonies(&mut self, _harmonies: Harmonies, _dominant: Option<f32>) {}
+    /// forget where the pixels fall, after their colors changed
+    fn clear_cache(&mut self);
+
+    /// collect distribution of the current slice
+    fn slice_stats(&mut self, img: &Image, weights: &PixelWeights) -> SliceStats {
+        let mut stats: SliceStats = BTreeMap::new();
         // this look takes the most time
-        for i in 0..w {
-            for j in 0..h {
-                let (relevant, data) = self.inspect(img, i, j);
-                if relevant {
-                    stats.insert(
-                        data,
-                        stats.get(&data).map(|i| i.to_owned() + 1).unwrap_or(1),
-                    );
-                }
+        for (i, j, weight) in weights.iter() {
+            let (relevant, data) = self.inspect(img, i, j);
+            if relevant {
+                *stats.entry(data).or_insert(0.) += weight as f64;
             }
         }
+        stats
+    }
 
-        let max_ = *stats.iter().max_by_key(|x| x.1).unwrap_or((&(0, 0), &0)).1;
-        let max = if max_ == 0 { 1 } else { max_ } as f32;
+    /// counts of every data point of every slice, as slice_stats would see them
+    fn global_bins(&mut self, img: &Image, weights: &PixelWeights) -> Vec<f32> {
+        let mut bins: HashMap<Bin, f64> = HashMap::new();
+        for (i, j, weight) in weights.iter() {
+            if let Some(located) = self.locate(img, i, j) {
+                *bins.entry(located).or_insert(0.) += weight as f64;
+            }
+        }
+        // a slice covers its own bin and the one below
+        bins.iter()
+            .map(|((slice, data), count)| {
+                let below = bins.get(&(slice - Self::DELTA as i64, *data));
+                (count + below.unwrap_or(&0.)) as f32
+            })
+            .collect()
+    }
+
+    /// draw 3d viz mesh, heights are mapped by scale
+    #[allow(clippy::identity_op)]
+    fn create_mesh(&self, stats: &SliceStats, scale: impl Fn(f32) -> f32) -> Mesh {
         let mut vtxs: Vec<[f32; 3]> = vec![];
         let mut indices: Vec<u32> = vec![];
         let (mut i, mut j, mut k) = (0, 0, 0);
@@ -84,7 +140,7 @@ pub trait Provider {
                 // draw cube
                 let base_x = i as f32 * Self::X_DELTA_N;
                 let base_z = j as f32 * Self::Z_DELTA_N;
-                let y = *stats.get(&(i, j)).unwrap_or(&0) as f32 / max;
+                let y = scale(*stats.get(&(i, j)).unwrap_or(&0.) as f32);
                 // top 4
                 let mut top_vtxs = vec![
                     [base_x - 0.5, y, base_z - 0.5],
@@ -134,24 +190,10 @@ pub trait Provider {
         .with_inserted_indices(Indices::U32(indices))
     }
 
-    /// returns the value for histogram given a pixel
-    fn convert(&self, pixel: Color) -> i64;
-    /// returns 2d histogram data for given image
-    fn histogram_data(&self, img: &Image) -> Vec<(f32, f32)> {
-        let mut result: BTreeMap<i64, i64> = BTreeMap::new();
-        let w = img.width();
-        let h = img.height();
-        for i in 0..w {
-            for j in 0..h {
-                let c = img.get_color_at(i, j).unwrap();
-                let h = self.convert(c);
-                result.insert(h, result.get(&h).map(|i| i.to_owned() + 1).unwrap_or(1));
-            }
-        }
-        // FIXME: might have accuracy issue
-        result
-            .iter()
-            .map(|(x, y)| (*x as f32, *y as f32 / (w * h) as f32))
-            .collect()
+    /// returns 2d histogram data for given image, and the ratio of achromatic pixels
+    fn histogram_data(&self, img: &Image, weights: &PixelWeights) -> (Vec<(f32, f32)>, f32) {
+        let colors = weights.iter().map(|(i, j, w)| (srgb_at(img, i, j), w));
+        let distribution = Distribution::from_weighted(Self::SPACE, self.achromatic(), colors);
+        (distribution.histogram(), distribution.achromatic_ratio())
     }
 }
@@ -3,11 +3,12 @@ use bevy::{
     render::render_resource::{AsBindGroup, ShaderRef},
     sprite::{AlphaMode2d, Material2d},
 };
+use sefenbu::space::{Bin, Coords, Space};
 use std::collections::HashMap;
 
-use crate::COLOR_3D_VIZ_COORD;
+use crate::{COLOR_3D_VIZ_COORD, simulation::Simulation};
 
-use super::generic::{CSpaceProvider, FromImage, Provider};
+use super::generic::{CSpaceProvider, FromImage, Provider, srgb_at};
 
 // global state
 #[derive(Resource)]
@@ -15,7 +16,7 @@ pub struct HslProvider {
     pub filter: HslMaterial,
     pub viz2d_material: Hsl2DVizMaterial,
     pub viz3d_material: Hsl3DVizMaterial,
-    cache: HashMap<(u32, u32), Hsla>,
+    cache: HashMap<(u32, u32), Coords>,
 }
 
 impl CSpaceProvider for HslProvider {
@@ -47,17 +48,18 @@ impl FromImage for HslProvider {
     }
 }
 
-const HSL_DELTA: f32 = 1.;
-impl Provider for HslProvider {
-    const MAX: f32 = 100.;
-    const MIN: f32 = 0.;
-    const DELTA: f32 = HSL_DELTA;
+impl HslProvider {
+    /// cache colors
+    fn coords_at(&mut self, img: &Image, i: u32, j: u32) -> Coords {
+        *self
+            .cache
+            .entry((i, j))
+            .or_insert_with(|| Self::SPACE.coords(srgb_at(img, i, j)))
+    }
+}
 
-    // X hue, Z saturation
-    const X_MAX: f32 = 360.;
-    const X_DELTA: f32 = 7.2;
-    const Z_MAX: f32 = 100.;
-    const Z_DELTA: f32 = 2.;
+impl Provider for HslProvider {
+    const SPACE: Space = Space::Hsl;
 
     #[rustfmt::skip]
     fn current(&self) -> f32 { self.filter.l }
@@ -83,34 +85,33 @@ impl Provider for HslProvider {
         self.viz3d_material.l = new_adjusted;
     }
 
-    fn convert(&self, c: Color) -> i64 {
-        let hsl: Hsla = c.into();
-        (hsl.lightness * Self::MAX / Self::DELTA) as i64 * (Self::DELTA as i64)
-    }
-
     fn inspect(&mut self, img: &Image, i: u32, j: u32) -> (bool, (i64, i64)) {
-        // cache colors
-        let hsl = match self.cache.get(&(i, j)) {
-            Some(c) => *c,
-            None => {
-                let c: Hsla = img.get_color_at(i, j).unwrap().into();
-                self.cache.insert((i, j), c);
-                c
-            }
-        };
-        if (hsl.lightness * Self::MAX - self.current()).abs() > Self::DELTA {
+        let coords = self.coords_at(img, i, j);
+        if (coords.param - self.current()).abs() < Self::DELTA {
             // within range
-            (
-                true,
-                (
-                    (hsl.hue / Self::X_DELTA) as i64 * (Self::X_DELTA as i64),
-                    (hsl.saturation * Self::Z_MAX / Self::Z_DELTA) as i64 * (Self::Z_DELTA as i64),
-                ),
-            )
+            (true, Self::SPACE.bin(coords).1)
         } else {
             (false, (0, 0))
         }
     }
+
+    fn locate(&mut self, img: &Image, i: u32, j: u32) -> Option<Bin> {
+        Some(Self::SPACE.bin(self.coords_at(img, i, j)))
+    }
+
+    fn set_mask(&mut self, mask: Handle<Image>, invert: bool) {
+        self.filter.mask_texture = mask;
+        self.filter.invert_mask = if invert { 1. } else { 0. };
+    }
+
+    fn set_simulation(&mut self, simulation: Simulation) {
+        self.filter.cvd = simulation.shader_index();
+        self.filter.cvd_analysis = if simulation.analysis { 1. } else { 0. };
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
 }
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
@@ -122,6 +123,15 @@ pub struct HslMaterial {
     #[texture(2)]
     #[sampler(3)]
     pub color_texture: Handle<Image>,
+    #[texture(5)]
+    #[sampler(6)]
+    pub mask_texture: Handle<Image>,
+    #[uniform(7)]
+    pub invert_mask: f32,
+    #[uniform(8)]
+    pub cvd: u32,
+    #[uniform(9)]
+    pub cvd_analysis: f32,
     _alpha_mode: AlphaMode2d,
 }
 
@@ -129,8 +139,14 @@ impl FromImage for HslMaterial {
     fn from_image(image: Handle<Image>) -> Self {
         HslMaterial {
             l: 100.,
-            delta: HSL_DELTA,
+            delta: HslProvider::DELTA,
             color_texture: image,
+            // white, analyze everything
+            mask_texture: Handle::default(),
+            invert_mask: 0.,
+            // normal vision
+            cvd: 0,
+            cvd_analysis: 0.,
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -138,7 +154,7 @@ impl FromImage for HslMaterial {
 
 impl Material2d for HslMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/hsl.wgsl".into()
+        "shaders://hsl.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -159,7 +175,7 @@ impl Default for Hsl2DVizMaterial {
     fn default() -> Self {
         Hsl2DVizMaterial {
             l: 100.,
-            delta: HSL_DELTA,
+            delta: HslProvider::DELTA,
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -167,7 +183,7 @@ impl Default for Hsl2DVizMaterial {
 
 impl Material2d for Hsl2DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/hsl_2dviz.wgsl".into()
+        "shaders://hsl_2dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -190,7 +206,7 @@ impl Default for Hsl3DVizMaterial {
     fn default() -> Self {
         Hsl3DVizMaterial {
             l: 100.,
-            delta: HSL_DELTA,
+            delta: HslProvider::DELTA,
             bottom: COLOR_3D_VIZ_COORD - Vec3::new(0.5, 0.5, 0.5),
             _alpha_mode: AlphaMode::AlphaToCoverage,
         }
@@ -198,7 +214,7 @@ impl Default for Hsl3DVizMaterial {
 }
 impl Material for Hsl3DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/hsl_3dviz.wgsl".into()
+        "shaders://hsl_3dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode {
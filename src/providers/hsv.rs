@@ -1,12 +1,13 @@
-use crate::COLOR_3D_VIZ_COORD;
+use crate::{COLOR_3D_VIZ_COORD, harmony_overlay::Harmonies, simulation::Simulation};
 use bevy::{
     prelude::*,
     render::render_resource::{AsBindGroup, ShaderRef},
     sprite::{AlphaMode2d, Material2d},
 };
+use sefenbu::space::{Bin, Coords, Space};
 use std::collections::HashMap;
 
-use super::generic::{CSpaceProvider, FromImage, Provider};
+use super::generic::{CSpaceProvider, FromImage, Provider, srgb_at};
 
 // global state
 #[derive(Resource)]
@@ -14,7 +15,13 @@ pub struct HsvProvider {
     pub filter: HsvMaterial,
     pub viz2d_material: Hsv2DVizMaterial,
     pub viz3d_material: Hsv3DVizMaterial,
-    cache: HashMap<(u32, u32), Hsva>,
+    cache: HashMap<(u32, u32), Coords>,
+    /// chroma below which the hue is meaningless
+    achromatic: f32,
+    keep_achromatic: bool,
+    harmonies: Harmonies,
+    /// middle of the slice with the most weight, see sefenbu::harmony::dominant_hue
+    dominant: Option<f32>,
 }
 
 impl CSpaceProvider for HsvProvider {
@@ -42,22 +49,33 @@ impl FromImage for HsvProvider {
             viz2d_material: Hsv2DVizMaterial::default(),
             viz3d_material: Hsv3DVizMaterial::default(),
             cache: HashMap::new(),
+            achromatic: 0.,
+            keep_achromatic: true,
+            harmonies: Harmonies::default(),
+            dominant: None,
         }
     }
 }
 
-const HSV_DELTA: f32 = 2.;
-const HSV_SV_MAX: f32 = 100.;
-const HSV_SV_DELTA: f32 = 2.;
-impl Provider for HsvProvider {
-    const MAX: f32 = 360.;
-    const MIN: f32 = 0.;
-    const DELTA: f32 = HSV_DELTA;
+impl HsvProvider {
+    /// cache colors
+    fn coords_at(&mut self, img: &Image, i: u32, j: u32) -> Coords {
+        *self
+            .cache
+            .entry((i, j))
+            .or_insert_with(|| Self::SPACE.coords(srgb_at(img, i, j)))
+    }
+
+    /// the harmony follows the current hue
+    fn update_harmony(&mut self) {
+        let hues = self.harmonies.uniform(self.current(), self.dominant);
+        self.filter.harmony = hues;
+        self.viz2d_material.harmony = hues;
+    }
+}
 
-    const X_MAX: f32 = HSV_SV_MAX;
-    const X_DELTA: f32 = HSV_SV_DELTA;
-    const Z_MAX: f32 = HSV_SV_MAX;
-    const Z_DELTA: f32 = HSV_SV_DELTA;
+impl Provider for HsvProvider {
+    const SPACE: Space = Space::Hsv;
 
     #[rustfmt::skip]
     fn current(&self) -> f32 { self.filter.h }
@@ -67,6 +85,7 @@ impl Provider for HsvProvider {
         self.filter.h = Self::MIN.max(self.filter.h - change);
         self.viz2d_material.h = Self::MIN.max(self.viz2d_material.h - change);
         self.viz3d_material.h = Self::MIN.max(self.viz3d_material.h - change);
+        self.update_harmony();
     }
 
     fn incr(&mut self, change: f32) {
@@ -74,6 +93,7 @@ impl Provider for HsvProvider {
         self.filter.h = Self::MAX.min(self.filter.h + change);
         self.viz2d_material.h = Self::MAX.min(self.viz2d_material.h + change);
         self.viz3d_material.h = Self::MAX.min(self.viz3d_material.h + change);
+        self.update_harmony();
     }
 
     fn set(&mut self, new: f32) {
@@ -81,37 +101,55 @@ impl Provider for HsvProvider {
         self.filter.h = new_adjusted;
         self.viz2d_material.h = new_adjusted;
         self.viz3d_material.h = new_adjusted;
-    }
-
-    fn convert(&self, c: Color) -> i64 {
-        let hsv: Hsva = c.into();
-        (hsv.hue / Self::DELTA) as i64 * (Self::DELTA as i64)
+        self.update_harmony();
     }
 
     fn inspect(&mut self, img: &Image, i: u32, j: u32) -> (bool, (i64, i64)) {
-        // cache colors
-        let hsv = match self.cache.get(&(i, j)) {
-            Some(c) => *c,
-            None => {
-                let c: Hsva = img.get_color_at(i, j).unwrap().into();
-                self.cache.insert((i, j), c);
-                c
-            }
-        };
-        if (hsv.hue - self.current()).abs() > Self::DELTA {
+        let hue = self.coords_at(img, i, j).param;
+        match self.locate(img, i, j) {
             // within range
-            (
-                true,
-                (
-                    (Into::<f32>::into(hsv.saturation) * HSV_SV_MAX / HSV_SV_DELTA) as i64
-                        * (HSV_SV_DELTA as i64),
-                    (Into::<f32>::into(hsv.value) * HSV_SV_MAX / HSV_SV_DELTA) as i64
-                        * (HSV_SV_DELTA as i64),
-                ),
-            )
-        } else {
-            (false, (0, 0))
+            Some((_, data)) if (hue - self.current()).abs() < Self::DELTA => (true, data),
+            _ => (false, (0, 0)),
+        }
+    }
+
+    fn locate(&mut self, img: &Image, i: u32, j: u32) -> Option<Bin> {
+        let coords = self.coords_at(img, i, j);
+        if !self.keep_achromatic && Self::SPACE.is_achromatic(coords, self.achromatic) {
+            return None;
         }
+        Some(Self::SPACE.bin(coords))
+    }
+
+    fn set_achromatic(&mut self, threshold: f32, keep: bool) {
+        self.achromatic = threshold;
+        self.keep_achromatic = keep;
+        // the filter only needs to know about pixels left out of the slices
+        self.filter.achromatic = if keep { 0. } else { threshold };
+    }
+
+    fn achromatic(&self) -> f32 {
+        self.achromatic
+    }
+
+    fn set_mask(&mut self, mask: Handle<Image>, invert: bool) {
+        self.filter.mask_texture = mask;
+        self.filter.invert_mask = if invert { 1. } else { 0. };
+    }
+
+    fn set_simulation(&mut self, simulation: Simulation) {
+        self.filter.cvd = simulation.shader_index();
+        self.filter.cvd_analysis = if simulation.analysis { 1. } else { 0. };
+    }
+
+    fn set_harmonies(&mut self, harmonies: Harmonies, dominant: Option<f32>) {
+        self.harmonies = harmonies;
+        self.dominant = dominant;
+        self.update_harmony();
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache.clear();
     }
 }
 
@@ -124,6 +162,20 @@ pub struct HsvMaterial {
     #[texture(2)]
     #[sampler(3)]
     pub color_texture: Handle<Image>,
+    #[uniform(4)]
+    pub achromatic: f32,
+    #[texture(5)]
+    #[sampler(6)]
+    pub mask_texture: Handle<Image>,
+    #[uniform(7)]
+    pub invert_mask: f32,
+    #[uniform(8)]
+    pub cvd: u32,
+    #[uniform(9)]
+    pub cvd_analysis: f32,
+    /// hues in harmony with the current one, unused ones negative
+    #[uniform(10)]
+    pub harmony: Vec4,
     _alpha_mode: AlphaMode2d,
 }
 
@@ -131,8 +183,16 @@ impl FromImage for HsvMaterial {
     fn from_image(image: Handle<Image>) -> Self {
         HsvMaterial {
             h: 360.,
-            delta: HSV_DELTA,
+            delta: HsvProvider::DELTA,
             color_texture: image,
+            achromatic: 0.,
+            // white, analyze everything
+            mask_texture: Handle::default(),
+            invert_mask: 0.,
+            // normal vision
+            cvd: 0,
+            cvd_analysis: 0.,
+            harmony: Vec4::splat(-1.),
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -140,7 +200,7 @@ impl FromImage for HsvMaterial {
 
 impl Material2d for HsvMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/hsv.wgsl".into()
+        "shaders://hsv.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -154,6 +214,9 @@ pub struct Hsv2DVizMaterial {
     pub h: f32,
     #[uniform(1)]
     pub delta: f32,
+    /// hues in harmony with the current one, unused ones negative
+    #[uniform(2)]
+    pub harmony: Vec4,
     _alpha_mode: AlphaMode2d,
 }
 
@@ -161,7 +224,8 @@ impl Default for Hsv2DVizMaterial {
     fn default() -> Self {
         Hsv2DVizMaterial {
             h: 360.,
-            delta: HSV_DELTA,
+            delta: HsvProvider::DELTA,
+            harmony: Vec4::splat(-1.),
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -169,7 +233,7 @@ impl Default for Hsv2DVizMaterial {
 
 impl Material2d for Hsv2DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/hsv_2dviz.wgsl".into()
+        "shaders://hsv_2dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -192,7 +256,7 @@ impl Default for Hsv3DVizMaterial {
     fn default() -> Self {
         Hsv3DVizMaterial {
             h: 360.,
-            delta: HSV_DELTA,
+            delta: HsvProvider::DELTA,
             bottom: COLOR_3D_VIZ_COORD - Vec3::new(0.5, 0.5, 0.5),
             _alpha_mode: AlphaMode::AlphaToCoverage,
         }
@@ -200,7 +264,7 @@ impl Default for Hsv3DVizMaterial {
 }
 impl Material for Hsv3DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/hsv_3dviz.wgsl".into()
+        "shaders://hsv_3dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode {
@@ -3,12 +3,12 @@ use bevy::{
     render::render_resource::{AsBindGroup, ShaderRef},
     sprite::{AlphaMode2d, Material2d},
 };
-use palette::{FromColor, Okhsl, Srgb};
+use sefenbu::space::{Bin, Coords, Space};
 use std::collections::HashMap;
 
-use crate::COLOR_3D_VIZ_COORD;
+use crate::{COLOR_3D_VIZ_COORD, simulation::Simulation};
 
-use super::generic::{CSpaceProvider, FromImage, Provider};
+use super::generic::{CSpaceProvider, FromImage, Provider, srgb_at};
 
 // global state
 #[derive(Resource)]
@@ -16,7 +16,7 @@ pub struct OkhslProvider {
     pub filter: OkhslMaterial,
     pub viz2d_material: Okhsl2DVizMaterial,
     pub viz3d_material: Okhsl3DVizMaterial,
-    cache: HashMap<(u32, u32), Okhsl>,
+    cache: HashMap<(u32, u32), Coords>,
 }
 
 impl CSpaceProvider for OkhslProvider {
@@ -48,22 +48,18 @@ impl FromImage for OkhslProvider {
     }
 }
 
-fn to_okhsl(c: Color) -> Okhsl {
-    let s: Srgba = c.into();
-    Okhsl::from_color(Srgb::new(s.red, s.green, s.blue))
+impl OkhslProvider {
+    /// cache colors
+    fn coords_at(&mut self, img: &Image, i: u32, j: u32) -> Coords {
+        *self
+            .cache
+            .entry((i, j))
+            .or_insert_with(|| Self::SPACE.coords(srgb_at(img, i, j)))
+    }
 }
 
-const OKHSL_DELTA: f32 = 1.;
 impl Provider for OkhslProvider {
-    const MAX: f32 = 100.;
-    const MIN: f32 = 0.;
-    const DELTA: f32 = OKHSL_DELTA;
-
-    // X hue, Z saturation
-    const X_MAX: f32 = 360.;
-    const X_DELTA: f32 = 7.2;
-    const Z_MAX: f32 = 100.;
-    const Z_DELTA: f32 = 2.;
+    const SPACE: Space = Space::Okhsl;
 
     #[rustfmt::skip]
     fn current(&self) -> f32 { self.filter.l }
@@ -89,36 +85,33 @@ impl Provider for OkhslProvider {
         self.viz3d_material.l = new_adjusted;
     }
 
-    fn convert(&self, c: Color) -> i64 {
-        let okhsl: Okhsl = to_okhsl(c);
-        (okhsl.lightness * Self::MAX / Self::DELTA) as i64 * (Self::DELTA as i64)
-    }
-
     fn inspect(&mut self, img: &Image, i: u32, j: u32) -> (bool, (i64, i64)) {
-        // cache colors
-        let okhsl = match self.cache.get(&(i, j)) {
-            Some(c) => *c,
-            None => {
-                let c = to_okhsl(img.get_color_at(i, j).unwrap());
-                self.cache.insert((i, j), c);
-                c
-            }
-        };
-        if (okhsl.lightness * Self::MAX - self.current()).abs() < Self::DELTA {
+        let coords = self.coords_at(img, i, j);
+        if (coords.param - self.current()).abs() < Self::DELTA {
             // within range
-            (
-                true,
-                (
-                    (okhsl.hue.into_positive_degrees() / Self::X_DELTA) as i64
-                        * (Self::X_DELTA as i64),
-                    (Into::<f32>::into(okhsl.saturation) * Self::Z_MAX / Self::Z_DELTA) as i64
-                        * (Self::Z_DELTA as i64),
-                ),
-            )
+            (true, Self::SPACE.bin(coords).1)
         } else {
             (false, (0, 0))
         }
     }
+
+    fn locate(&mut self, img: &Image, i: u32, j: u32) -> Option<Bin> {
+        Some(Self::SPACE.bin(self.coords_at(img, i, j)))
+    }
+
+    fn set_mask(&mut self, mask: Handle<Image>, invert: bool) {
+        self.filter.mask_texture = mask;
+        self.filter.invert_mask = if invert { 1. } else { 0. };
+    }
+
+    fn set_simulation(&mut self, simulation: Simulation) {
+        self.filter.cvd = simulation.shader_index();
+        self.filter.cvd_analysis = if simulation.analysis { 1. } else { 0. };
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
 }
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
@@ -130,6 +123,15 @@ pub struct OkhslMaterial {
     #[texture(2)]
     #[sampler(3)]
     pub color_texture: Handle<Image>,
+    #[texture(5)]
+    #[sampler(6)]
+    pub mask_texture: Handle<Image>,
+    #[uniform(7)]
+    pub invert_mask: f32,
+    #[uniform(8)]
+    pub cvd: u32,
+    #[uniform(9)]
+    pub cvd_analysis: f32,
     _alpha_mode: AlphaMode2d,
 }
 
@@ -137,8 +139,14 @@ impl FromImage for OkhslMaterial {
     fn from_image(image: Handle<Image>) -> Self {
         OkhslMaterial {
             l: 100.,
-            delta: OKHSL_DELTA,
+            delta: OkhslProvider::DELTA,
             color_texture: image,
+            // white, analyze everything
+            mask_texture: Handle::default(),
+            invert_mask: 0.,
+            // normal vision
+            cvd: 0,
+            cvd_analysis: 0.,
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -146,7 +154,7 @@ impl FromImage for OkhslMaterial {
 
 impl Material2d for OkhslMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/okhsl.wgsl".into()
+        "shaders://okhsl.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -167,7 +175,7 @@ impl Default for Okhsl2DVizMaterial {
     fn default() -> Self {
         Okhsl2DVizMaterial {
             l: 100.,
-            delta: OKHSL_DELTA,
+            delta: OkhslProvider::DELTA,
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -175,7 +183,7 @@ impl Default for Okhsl2DVizMaterial {
 
 impl Material2d for Okhsl2DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/okhsl_2dviz.wgsl".into()
+        "shaders://okhsl_2dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -198,7 +206,7 @@ impl Default for Okhsl3DVizMaterial {
     fn default() -> Self {
         Okhsl3DVizMaterial {
             l: 100.,
-            delta: OKHSL_DELTA,
+            delta: OkhslProvider::DELTA,
             bottom: COLOR_3D_VIZ_COORD - Vec3::new(0.5, 0.5, 0.5),
             _alpha_mode: AlphaMode::AlphaToCoverage,
         }
@@ -206,7 +214,7 @@ impl Default for Okhsl3DVizMaterial {
 }
 impl Material for Okhsl3DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/okhsl_3dviz.wgsl".into()
+        "shaders://okhsl_3dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode {
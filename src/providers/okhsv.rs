@@ -3,12 +3,12 @@ use bevy::{
     render::render_resource::{AsBindGroup, ShaderRef},
     sprite::{AlphaMode2d, Material2d},
 };
-use palette::{FromColor, Okhsv, Srgb};
+use sefenbu::space::{Bin, Coords, Space};
 use std::collections::HashMap;
 
-use crate::COLOR_3D_VIZ_COORD;
+use crate::{COLOR_3D_VIZ_COORD, harmony_overlay::Harmonies, simulation::Simulation};
 
-use super::generic::{CSpaceProvider, FromImage, Provider};
+use super::generic::{CSpaceProvider, FromImage, Provider, srgb_at};
 
 // global state
 #[derive(Resource)]
@@ -16,7 +16,13 @@ pub struct OkhsvProvider {
     pub filter: OkhsvMaterial,
     pub viz2d_material: Okhsv2DVizMaterial,
     pub viz3d_material: Okhsv3DVizMaterial,
-    cache: HashMap<(u32, u32), Okhsv>,
+    cache: HashMap<(u32, u32), Coords>,
+    /// chroma below which the hue is meaningless
+    achromatic: f32,
+    keep_achromatic: bool,
+    harmonies: Harmonies,
+    /// middle of the slice with the most weight, see sefenbu::harmony::dominant_hue
+    dominant: Option<f32>,
 }
 
 impl CSpaceProvider for OkhsvProvider {
@@ -44,27 +50,33 @@ impl FromImage for OkhsvProvider {
             viz2d_material: Okhsv2DVizMaterial::default(),
             viz3d_material: Okhsv3DVizMaterial::default(),
             cache: HashMap::new(),
+            achromatic: 0.,
+            keep_achromatic: true,
+            harmonies: Harmonies::default(),
+            dominant: None,
         }
     }
 }
 
-fn to_okhsv(c: Color) -> Okhsv {
-    let s: Srgba = c.into();
-    Okhsv::from_color(Srgb::new(s.red, s.green, s.blue))
+impl OkhsvProvider {
+    /// cache colors
+    fn coords_at(&mut self, img: &Image, i: u32, j: u32) -> Coords {
+        *self
+            .cache
+            .entry((i, j))
+            .or_insert_with(|| Self::SPACE.coords(srgb_at(img, i, j)))
+    }
+
+    /// the harmony follows the current hue
+    fn update_harmony(&mut self) {
+        let hues = self.harmonies.uniform(self.current(), self.dominant);
+        self.filter.harmony = hues;
+        self.viz2d_material.harmony = hues;
+    }
 }
 
-const OKHSV_DELTA: f32 = 2.;
-const OKHSV_SV_MAX: f32 = 100.;
-const OKHSV_SV_DELTA: f32 = 2.;
 impl Provider for OkhsvProvider {
-    const MAX: f32 = 360.;
-    const MIN: f32 = 0.;
-    const DELTA: f32 = OKHSV_DELTA;
-
-    const X_MAX: f32 = OKHSV_SV_MAX;
-    const X_DELTA: f32 = OKHSV_SV_DELTA;
-    const Z_MAX: f32 = OKHSV_SV_MAX;
-    const Z_DELTA: f32 = OKHSV_SV_DELTA;
+    const SPACE: Space = Space::Okhsv;
 
     #[rustfmt::skip]
     fn current(&self) -> f32 { self.filter.h }
@@ -74,6 +86,7 @@ impl Provider for OkhsvProvider {
         self.filter.h = Self::MIN.max(self.filter.h - change);
         self.viz2d_material.h = Self::MIN.max(self.viz2d_material.h - change);
         self.viz3d_material.h = Self::MIN.max(self.viz3d_material.h - change);
+        self.update_harmony();
     }
 
     fn incr(&mut self, change: f32) {
@@ -81,6 +94,7 @@ impl Provider for OkhsvProvider {
         self.filter.h = Self::MAX.min(self.filter.h + change);
         self.viz2d_material.h = Self::MAX.min(self.viz2d_material.h + change);
         self.viz3d_material.h = Self::MAX.min(self.viz3d_material.h + change);
+        self.update_harmony();
     }
 
     fn set(&mut self, new: f32) {
@@ -88,38 +102,56 @@ impl Provider for OkhsvProvider {
         self.filter.h = new_adjusted;
         self.viz2d_material.h = new_adjusted;
         self.viz3d_material.h = new_adjusted;
-    }
-
-    fn convert(&self, c: Color) -> i64 {
-        let okhsv: Okhsv = to_okhsv(c);
-        (okhsv.hue.into_positive_degrees() / Self::DELTA) as i64 * (Self::DELTA as i64)
+        self.update_harmony();
     }
 
     fn inspect(&mut self, img: &Image, i: u32, j: u32) -> (bool, (i64, i64)) {
-        // cache colors
-        let okhsv = match self.cache.get(&(i, j)) {
-            Some(c) => *c,
-            None => {
-                let c = to_okhsv(img.get_color_at(i, j).unwrap());
-                self.cache.insert((i, j), c);
-                c
-            }
-        };
-        if (okhsv.hue.into_positive_degrees() - self.current()).abs() < Self::DELTA {
+        let hue = self.coords_at(img, i, j).param;
+        match self.locate(img, i, j) {
             // within range
-            (
-                true,
-                (
-                    (Into::<f32>::into(okhsv.saturation) * OKHSV_SV_MAX / OKHSV_SV_DELTA) as i64
-                        * (OKHSV_SV_DELTA as i64),
-                    (Into::<f32>::into(okhsv.value) * OKHSV_SV_MAX / OKHSV_SV_DELTA) as i64
-                        * (OKHSV_SV_DELTA as i64),
-                ),
-            )
-        } else {
-            (false, (0, 0))
+            Some((_, data)) if (hue - self.current()).abs() < Self::DELTA => (true, data),
+            _ => (false, (0, 0)),
         }
     }
+
+    fn locate(&mut self, img: &Image, i: u32, j: u32) -> Option<Bin> {
+        let coords = self.coords_at(img, i, j);
+        if !self.keep_achromatic && Self::SPACE.is_achromatic(coords, self.achromatic) {
+            return None;
+        }
+        Some(Self::SPACE.bin(coords))
+    }
+
+    fn set_achromatic(&mut self, threshold: f32, keep: bool) {
+        self.achromatic = threshold;
+        self.keep_achromatic = keep;
+        // the filter only needs to know about pixels left out of the slices
+        self.filter.achromatic = if keep { 0. } else { threshold };
+    }
+
+    fn achromatic(&self) -> f32 {
+        self.achromatic
+    }
+
+    fn set_mask(&mut self, mask: Handle<Image>, invert: bool) {
+        self.filter.mask_texture = mask;
+        self.filter.invert_mask = if invert { 1. } else { 0. };
+    }
+
+    fn set_simulation(&mut self, simulation: Simulation) {
+        self.filter.cvd = simulation.shader_index();
+        self.filter.cvd_analysis = if simulation.analysis { 1. } else { 0. };
+    }
+
+    fn set_harmonies(&mut self, harmonies: Harmonies, dominant: Option<f32>) {
+        self.harmonies = harmonies;
+        self.dominant = dominant;
+        self.update_harmony();
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
 }
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
@@ -131,6 +163,20 @@ pub struct OkhsvMaterial {
     #[texture(2)]
     #[sampler(3)]
     pub color_texture: Handle<Image>,
+    #[uniform(4)]
+    pub achromatic: f32,
+    #[texture(5)]
+    #[sampler(6)]
+    pub mask_texture: Handle<Image>,
+    #[uniform(7)]
+    pub invert_mask: f32,
+    #[uniform(8)]
+    pub cvd: u32,
+    #[uniform(9)]
+    pub cvd_analysis: f32,
+    /// hues in harmony with the current one, unused ones negative
+    #[uniform(10)]
+    pub harmony: Vec4,
     _alpha_mode: AlphaMode2d,
 }
 
@@ -138,8 +184,16 @@ impl FromImage for OkhsvMaterial {
     fn from_image(image: Handle<Image>) -> Self {
         OkhsvMaterial {
             h: 360.,
-            delta: OKHSV_DELTA,
+            delta: OkhsvProvider::DELTA,
             color_texture: image,
+            achromatic: 0.,
+            // white, analyze everything
+            mask_texture: Handle::default(),
+            invert_mask: 0.,
+            // normal vision
+            cvd: 0,
+            cvd_analysis: 0.,
+            harmony: Vec4::splat(-1.),
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -147,7 +201,7 @@ impl FromImage for OkhsvMaterial {
 
 impl Material2d for OkhsvMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/okhsv.wgsl".into()
+        "shaders://okhsv.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -161,6 +215,9 @@ pub struct Okhsv2DVizMaterial {
     pub h: f32,
     #[uniform(1)]
     pub delta: f32,
+    /// hues in harmony with the current one, unused ones negative
+    #[uniform(2)]
+    pub harmony: Vec4,
     _alpha_mode: AlphaMode2d,
 }
 
@@ -168,7 +225,8 @@ impl Default for Okhsv2DVizMaterial {
     fn default() -> Self {
         Okhsv2DVizMaterial {
             h: 360.,
-            delta: OKHSV_DELTA,
+            delta: OkhsvProvider::DELTA,
+            harmony: Vec4::splat(-1.),
             _alpha_mode: AlphaMode2d::Blend,
         }
     }
@@ -176,7 +234,7 @@ impl Default for Okhsv2DVizMaterial {
 
 impl Material2d for Okhsv2DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/okhsv_2dviz.wgsl".into()
+        "shaders://okhsv_2dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
@@ -199,7 +257,7 @@ impl Default for Okhsv3DVizMaterial {
     fn default() -> Self {
         Okhsv3DVizMaterial {
             h: 360.,
-            delta: OKHSV_DELTA,
+            delta: OkhsvProvider::DELTA,
             bottom: COLOR_3D_VIZ_COORD - Vec3::new(0.5, 0.5, 0.5),
             _alpha_mode: AlphaMode::AlphaToCoverage,
         }
@@ -207,7 +265,7 @@ impl Default for Okhsv3DVizMaterial {
 }
 impl Material for Okhsv3DVizMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/okhsv_3dviz.wgsl".into()
+        "shaders://okhsv_3dviz.wgsl".into()
     }
 
     fn alpha_mode(&self) -> AlphaMode {
@@ -0,0 +1,176 @@
+use std::{fmt::Display, str::FromStr};
+
+use palette::{FromColor, Lab, Oklab, Srgb, color_difference::Ciede2000};
+
+use crate::Error;
+
+/// How the difference between two colors is measured, as a ΔE
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Metric {
+    /// euclidean distance in OKLab, times 100 to be read on about the same scale as CIEDE2000
+    Oklab,
+    /// CIEDE2000 in CIELAB under D65
+    Ciede2000,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 2] = [Metric::Oklab, Metric::Ciede2000];
+
+    /// lowercase name, as taken by FromStr
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Oklab => "oklab",
+            Metric::Ciede2000 => "ciede2000",
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Metric {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Metric::ALL
+            .into_iter()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| Error::UnknownMetric(s.to_string()))
+    }
+}
+
+/// The colors written as #rrggbb or #rgb in a text, in order, e.g. one per line or the
+/// Xresources of a terminal scheme. Anything else is skipped
+pub fn parse_palette(text: &str) -> Result<Vec<Srgb>, Error> {
+    let colors: Vec<Srgb> = text
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '#'))
+        .filter(|token| token.starts_with('#'))
+        .filter_map(|token| Srgb::<u8>::from_str(token).ok())
+        .map(|c| c.into_format())
+        .collect();
+    if colors.is_empty() {
+        return Err(Error::EmptyPalette);
+    }
+    Ok(colors)
+}
+
+/// Finds the palette color nearest to a color
+#[derive(Clone, Debug)]
+pub struct Quantizer {
+    metric: Metric,
+    palette: Vec<Srgb>,
+    /// the palette in the space the metric measures in
+    oklab: Vec<Oklab>,
+    lab: Vec<Lab>,
+}
+
+impl Quantizer {
+    pub fn new(palette: &[Srgb], metric: Metric) -> Self {
+        Quantizer {
+            metric,
+            palette: palette.to_vec(),
+            oklab: palette.iter().map(|c| Oklab::from_color(*c)).collect(),
+            lab: palette.iter().map(|c| Lab::from_color(*c)).collect(),
+        }
+    }
+
+    pub fn palette(&self) -> &[Srgb] {
+        &self.palette
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// index of the nearest palette color and the ΔE to it, None with an empty palette
+    pub fn nearest(&self, color: Srgb) -> Option<(usize, f32)> {
+        let distances: Vec<f32> = match self.metric {
+            Metric::Oklab => {
+                let c = Oklab::from_color(color);
+                let distance = |p: &Oklab| {
+                    let (l, a, b) = (c.l - p.l, c.a - p.a, c.b - p.b);
+                    (l * l + a * a + b * b).sqrt() * 100.
+                };
+                self.oklab.iter().map(distance).collect()
+            }
+            Metric::Ciede2000 => {
+                let c = Lab::from_color(color);
+                self.lab.iter().map(|p| c.difference(*p)).collect()
+            }
+        };
+        distances
+            .into_iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// How far the colors of an image are from their nearest palette color
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ErrorStats {
+    pub mean: f32,
+    /// the ΔE 95% of the weight is within
+    pub p95: f32,
+}
+
+impl ErrorStats {
+    /// from ΔEs along with how much each of them counts, None without any weight
+    pub fn from_weighted(errors: impl IntoIterator<Item = (f32, f32)>) -> Option<Self> {
+        let mut errors: Vec<(f32, f32)> = errors.into_iter().collect();
+        // in f64, as f32 stops adding unit weights past 2^24
+        let total: f64 = errors.iter().map(|(_, weight)| *weight as f64).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mean = errors
+            .iter()
+            .map(|(e, weight)| *e as f64 * *weight as f64)
+            .sum::<f64>()
+            / total;
+        errors.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut below = 0.;
+        let p95 = errors
+            .iter()
+            .find(|(_, weight)| {
+                below += *weight as f64;
+                below >= total * 0.95
+            })
+            .map_or(errors[errors.len() - 1].0, |(e, _)| *e);
+        Some(ErrorStats {
+            mean: mean as f32,
+            p95,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_from_text() {
+        let colors = parse_palette("*.color0: #000000\n*.color1: #f00\nnot #a color\n").unwrap();
+        assert_eq!(colors, vec![Srgb::new(0., 0., 0.), Srgb::new(1., 0., 0.)]);
+    }
+
+    #[test]
+    fn palette_without_colors() {
+        assert!(matches!(parse_palette(""), Err(Error::EmptyPalette)));
+        assert!(matches!(
+            parse_palette("#zzzzzz # #12345 red"),
+            Err(Error::EmptyPalette)
+        ));
+    }
+
+    #[test]
+    fn error_stats_above_f32_precision() {
+        let errors =
+            std::iter::once((1., 16_777_216.)).chain(std::iter::repeat_n((3., 1.), 2_000_000));
+        let stats = ErrorStats::from_weighted(errors).unwrap();
+        assert!((stats.mean - 1.2130).abs() < 1e-4);
+        assert_eq!(stats.p95, 3.);
+    }
+}
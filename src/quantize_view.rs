@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use palette::Srgb;
+use sefenbu::{
+    Metric,
+    quantize::{ErrorStats, Quantizer},
+};
+
+use crate::{providers::generic::srgb_at, swatches::Swatches, weights::PixelWeights};
+
+/// ΔE at which the heatmap is white, anything further is too
+pub const HEAT_MAX: f32 = 20.;
+
+/// What the image view shows over the image
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuantizeView {
+    /// every pixel as its nearest palette color
+    Palette,
+    /// how far every pixel is from its nearest palette color, see heat
+    Error,
+}
+
+/// The image quantized to a palette, shown over it
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Quantization {
+    /// nothing is shown without one
+    pub view: Option<QuantizeView>,
+    pub metric: Metric,
+    /// --palette, the picked swatches without one
+    pub palette: Option<Vec<Srgb>>,
+}
+
+impl Quantization {
+    /// the colors quantized to
+    pub fn palette<'a>(&'a self, swatches: &'a Swatches) -> &'a [Srgb] {
+        self.palette.as_deref().unwrap_or(&swatches.0)
+    }
+
+    /// show the quantized image, or hide whatever is shown
+    pub fn toggle(&mut self) {
+        self.view = match self.view {
+            None => Some(QuantizeView::Palette),
+            Some(_) => None,
+        };
+    }
+
+    /// show the error instead of the quantized image, or the other way round
+    pub fn toggle_error(&mut self) {
+        self.view = match self.view {
+            Some(QuantizeView::Error) => Some(QuantizeView::Palette),
+            _ => Some(QuantizeView::Error),
+        };
+    }
+
+    /// what is shown and how far the sample is from the palette, over the image, empty when
+    /// nothing is shown
+    pub fn note(&self, colors: usize, stats: Option<ErrorStats>) -> String {
+        let Some(view) = self.view else {
+            return String::new();
+        };
+        if colors == 0 {
+            return "nothing to quantize to, pass --palette or pick colors".to_string();
+        }
+        let shown = match view {
+            QuantizeView::Palette => format!("quantized to {} colors", colors),
+            QuantizeView::Error => {
+                format!("error to {} colors, white from ΔE {}", colors, HEAT_MAX)
+            }
+        };
+        match stats {
+            Some(stats) => format!(
+                "{}, {} ΔE mean {:.1}, 95th percentile {:.1}",
+                shown, self.metric, stats.mean, stats.p95
+            ),
+            None => shown,
+        }
+    }
+}
+
+/// black through red and yellow to white as the error grows to HEAT_MAX
+pub fn heat(error: f32) -> [u8; 3] {
+    let t = (error / HEAT_MAX).clamp(0., 1.) * 3.;
+    [t, t - 1., t - 2.].map(|c| (c.clamp(0., 1.) * 255.).round() as u8)
+}
+
+/// The nearest palette color of every color met so far, by its 8-bit value, since most images
+/// repeat their colors a lot
+pub struct NearestCache {
+    quantizer: Quantizer,
+    nearest: HashMap<[u8; 3], Option<(usize, f32)>>,
+}
+
+impl NearestCache {
+    pub fn new(palette: &[Srgb], metric: Metric) -> Self {
+        NearestCache {
+            quantizer: Quantizer::new(palette, metric),
+            nearest: HashMap::new(),
+        }
+    }
+
+    /// whether it quantizes to these colors this way
+    pub fn is_for(&self, palette: &[Srgb], metric: Metric) -> bool {
+        self.quantizer.palette() == palette && self.quantizer.metric() == metric
+    }
+
+    /// index of the nearest palette color and the ΔE to it, None with an empty palette
+    pub fn nearest(&mut self, color: Srgb) -> Option<(usize, f32)> {
+        let rgb = color.into_format::<u8>();
+        *self
+            .nearest
+            .entry([rgb.red, rgb.green, rgb.blue])
+            .or_insert_with(|| self.quantizer.nearest(color))
+    }
+
+    /// RGBA8 pixels of the image as the view shows them, keeping their alpha
+    pub fn render(&mut self, image: &Image, view: QuantizeView) -> Vec<u8> {
+        let mut data = Vec::with_capacity(image.width() as usize * image.height() as usize * 4);
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                let c = image.get_color_at(i, j).ok().map(Srgba::from);
+                let nearest = c.and_then(|c| {
+                    let nearest = self.nearest(Srgb::new(c.red, c.green, c.blue))?;
+                    Some((c.alpha, nearest))
+                });
+                let Some((alpha, (index, error))) = nearest else {
+                    data.extend([0; 4]);
+                    continue;
+                };
+                let rgb = match view {
+                    QuantizeView::Palette => {
+                        let c = self.quantizer.palette()[index].into_format::<u8>();
+                        [c.red, c.green, c.blue]
+                    }
+                    QuantizeView::Error => heat(error),
+                };
+                data.extend(rgb);
+                data.push((alpha * 255.).round() as u8);
+            }
+        }
+        data
+    }
+
+    /// how far the sample is from the palette, None with an empty palette or sample
+    pub fn stats(&mut self, image: &Image, weights: &PixelWeights) -> Option<ErrorStats> {
+        let errors: Vec<_> = weights
+            .iter()
+            .filter_map(|(i, j, weight)| Some((self.nearest(srgb_at(image, i, j))?.1, weight)))
+            .collect();
+        ErrorStats::from_weighted(errors)
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use bevy::prelude::Resource;
+use clap::ValueEnum;
+
+/// How bin counts are mapped to bar and column heights
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ScaleMode {
+    #[default]
+    Linear,
+    Log,
+    Sqrt,
+    // linear, clipped at a percentile of the non-empty bins
+    Percentile,
+}
+
+impl Display for ScaleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ScaleMode::Linear => "linear",
+            ScaleMode::Log => "log",
+            ScaleMode::Sqrt => "sqrt",
+            ScaleMode::Percentile => "percentile",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// What the 3d viz heights are relative to
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Normalization {
+    // the biggest bin of the current slice
+    #[default]
+    Slice,
+    // the biggest bin of any slice
+    Global,
+    // the number of pixels in the image
+    Total,
+}
+
+impl Display for Normalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Normalization::Slice => "slice",
+            Normalization::Global => "global",
+            Normalization::Total => "total",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Scaling {
+    pub mode: ScaleMode,
+    pub normalization: Normalization,
+    /// clipping point for ScaleMode::Percentile, (0,100]
+    pub percentile: f32,
+}
+
+impl Scaling {
+    pub fn cycle(&mut self) {
+        self.mode = match self.mode {
+            ScaleMode::Linear => ScaleMode::Log,
+            ScaleMode::Log => ScaleMode::Sqrt,
+            ScaleMode::Sqrt => ScaleMode::Percentile,
+            ScaleMode::Percentile => ScaleMode::Linear,
+        };
+    }
+
+    pub fn cycle_normalization(&mut self) {
+        self.normalization = match self.normalization {
+            Normalization::Slice => Normalization::Global,
+            Normalization::Global => Normalization::Total,
+            Normalization::Total => Normalization::Slice,
+        };
+    }
+
+    /// returns a function mapping each of the values to [0,1]
+    pub fn scaler(&self, values: &[f32]) -> impl Fn(f32) -> f32 + use<> {
+        let max = values.iter().copied().fold(0., f32::max);
+        // log is taken in multiples of the smallest bin, so counts and ratios look the same
+        let unit = values
+            .iter()
+            .copied()
+            .filter(|v| *v > 0.)
+            .fold(max, f32::min);
+        let mode = self.mode;
+        let top = match mode {
+            ScaleMode::Linear => max,
+            ScaleMode::Log if max > 0. => (max / unit).ln_1p(),
+            ScaleMode::Log => 0.,
+            ScaleMode::Sqrt => max.sqrt(),
+            ScaleMode::Percentile => {
+                let mut nonzero: Vec<f32> = values.iter().copied().filter(|v| *v > 0.).collect();
+                nonzero.sort_by(f32::total_cmp);
+                let rank = (nonzero.len() as f32 * self.percentile / 100.).ceil() as usize;
+                nonzero
+                    .get(rank.clamp(1, nonzero.len().max(1)) - 1)
+                    .copied()
+                    .unwrap_or(0.)
+            }
+        };
+
+        move |v| {
+            if top <= 0. {
+                return 0.;
+            }
+            let scaled = match mode {
+                ScaleMode::Linear | ScaleMode::Percentile => v / top,
+                ScaleMode::Log => (v / unit).ln_1p() / top,
+                ScaleMode::Sqrt => v.sqrt() / top,
+            };
+            scaled.min(1.)
+        }
+    }
+}
@@ -1,33 +1,137 @@
 use bevy::{
-    asset::LoadState,
+    asset::{LoadState, RenderAssetUsages},
     image::{ImageSampler, ImageSamplerDescriptor},
     prelude::*,
     render::{
         camera::Viewport,
-        render_resource::{AddressMode, FilterMode},
+        render_resource::{AddressMode, Extent3d, FilterMode, TextureDimension, TextureFormat},
     },
+    sprite::Anchor,
+    ui::RelativeCursorPosition,
 };
+use palette::Srgb;
+use sefenbu::{contrast, harmony::dominant_hue, scheme::ANSI_NAMES, Profile, Scheme};
 
 use crate::{
     cli::Cli,
-    controls::{ColorParam, KbdCooldown},
-    providers::generic::CSpaceProvider,
+    controls::{ColorParam, ImageViewConf, KbdCooldown},
+    decoded::Decoded,
+    exit_with_error,
+    frames::{timeline, Frames},
+    harmony_overlay::Harmonies,
+    hdr::{Exposure, HdrSource},
+    layout::Layout,
+    metadata::SourceProfile,
+    providers::generic::{srgb_at, CSpaceProvider},
+    quantize_view::{NearestCache, Quantization},
+    scaling::{Normalization, Scaling},
+    selection::{Roi, SelectTool, Selection},
+    simulation::Simulation,
+    swatches::Swatches,
+    terminal::TerminalScheme,
+    weights::{Mask, PixelWeights, Sampler},
     MeshControlConf,
 };
 
+/// The image with the filter on it, and the texture it shows
 #[derive(Component)]
-pub struct ImageCanvas;
+pub struct ImageCanvas(pub Handle<Image>);
 #[derive(Component)]
 pub struct Viz2DCanvas;
 #[derive(Component)]
 pub struct Viz3DMesh;
 
+#[derive(Component)]
+pub struct HistogramCover;
+#[derive(Component)]
+pub struct Banner;
+#[derive(Component)]
+pub struct SelectionHint;
+
 #[derive(Component)]
 pub struct ImageLoader(pub Handle<Image>);
+/// Highlights the pixels that were outside of sRGB, hidden until toggled
+#[derive(Component)]
+pub struct GamutOverlay;
+/// How bright a 16-bit or floating-point image is and how it is exposed, over the image
+#[derive(Component)]
+pub struct HdrNote;
+/// The color space the image was converted from, over the image
+#[derive(Component)]
+pub struct GamutNote;
+/// The color vision deficiency simulated, over the image
+#[derive(Component)]
+pub struct CvdNote;
+/// The hues in harmony with the current or the dominant one, over the image
+#[derive(Component)]
+pub struct HarmonyNote;
+/// The image quantized to the palette or its error, shown when toggled
+#[derive(Component)]
+pub struct QuantizeOverlay;
+/// What the quantization overlay shows and how far the sample is from the palette, over the image
+#[derive(Component)]
+pub struct QuantizeNote;
+/// The contrast of every pair of swatches
+#[derive(Component)]
+pub struct ContrastPanel;
+/// The terminal scheme made from the image
+#[derive(Component)]
+pub struct SchemePanel;
+/// The frame label and the timeline strip
+#[derive(Component)]
+pub struct Timeline;
+/// The 1d histogram of every frame, clicked or dragged on to pick one
+#[derive(Component)]
+pub struct TimelineStrip;
+/// The current frame on the timeline
+#[derive(Component)]
+pub struct TimelineMarker;
+/// Which frame is shown
+#[derive(Component)]
+pub struct TimelineLabel;
+/// --mask, on the same entity as the ImageLoader
+#[derive(Component)]
+pub struct MaskLoader(pub Handle<Image>);
 #[derive(Resource)]
 pub struct Background(pub Image);
+/// 1d histogram data and totals, computed once per image
+#[derive(Resource)]
+pub struct Histogram {
+    pub data: Vec<(f32, f32)>,
+    /// ratio of pixels without a meaningful param
+    pub achromatic: f32,
+    /// how much each pixel counts
+    pub weights: PixelWeights,
+    /// see Provider::global_bins, computed on first use
+    pub global_bins: Option<Vec<f32>>,
+    /// refinement level of the sample, see Sampler
+    pub level: usize,
+    /// a finer sample is still to come
+    pub refining: bool,
+}
 
-#[derive(Component)]
+impl Histogram {
+    fn new<A: CSpaceProvider>(
+        provider: &A,
+        image: &Image,
+        sampler: &Sampler,
+        level: usize,
+        roi: &Roi,
+    ) -> Self {
+        let weights = sampler.weights(image, level, roi.0.as_ref());
+        let (data, achromatic) = provider.histogram_data(image, &weights);
+        Histogram {
+            data,
+            achromatic,
+            weights,
+            global_bins: None,
+            level,
+            refining: level + 1 < sampler.levels(),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum CamViewPort {
     ImageFilter,
     Viz2d,
@@ -38,14 +142,31 @@ pub fn setup_scene_pre<A: CSpaceProvider>(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     opts: Res<Cli>,
+    frames: Option<ResMut<Frames>>,
+    decoded: Option<Res<Decoded>>,
+    mut images: ResMut<Assets<Image>>,
 ) {
-    // defer drawing of image
-    let img_handle: Handle<Image> = asset_server.load(&opts.file);
+    // defer drawing of image, frames and images from stdin or the clipboard are decoded already
+    let img_handle: Handle<Image> = if let Some(mut frames) = frames {
+        frames.texture = images.add(frames.images[frames.current].clone());
+        frames.texture.clone()
+    } else if let Some(decoded) = decoded {
+        commands.remove_resource::<Decoded>();
+        images.add(decoded.0.clone())
+    } else {
+        asset_server.load(opts.file.clone().unwrap_or_default())
+    };
     // associate the handle with an entity
-    commands.spawn(ImageLoader(img_handle.clone()));
+    let mut loader = commands.spawn(ImageLoader(img_handle.clone()));
 
     // create the global image filter shader
-    let p = A::from_image(img_handle.clone());
+    let mut p = A::from_image(img_handle.clone());
+    p.set_achromatic(opts.achromatic / 100., opts.keep_achromatic);
+    if let Some(mask) = &opts.mask {
+        let mask_handle: Handle<Image> = asset_server.load(mask);
+        p.set_mask(mask_handle.clone(), opts.invert_mask);
+        loader.insert(MaskLoader(mask_handle));
+    }
 
     // create the controls, consisting of the keybind timeout timer and the current value of the
     // params
@@ -61,59 +182,171 @@ const IMG_BASE_SIZE: f32 = 1080. * 4. / 5.;
 const COLOR_2D_VIZ_COORD: Vec3 = Vec3::new(2000., 0., 0.);
 const COLOR_2D_VIZ_SIZE: f32 = 350.;
 pub const COLOR_3D_VIZ_COORD: Vec3 = Vec3::new(-2000., 0., 0.);
+const ACHROMATIC_BAR_HEIGHT: f32 = 8.;
+const SELECTION_COLOR: Color = Color::srgb(1., 0.85, 0.);
+/// pixels outside of sRGB in the gamut overlay
+const GAMUT_COLOR: [u8; 4] = [255, 0, 255, 200];
+const DRAWING_COLOR: Color = Color::WHITE;
+/// behind the timeline, where frames have nothing in a slice
+const TIMELINE_BACKGROUND: Color = Color::srgba(0., 0., 0., 0.6);
+/// of the frame label and strip together
+const TIMELINE_HEIGHT: f32 = 96.;
+/// contrast values below the thresholds for body text
+const FAIL_COLOR: Color = Color::srgb(1., 0.45, 0.45);
+/// side of a swatch in the contrast panel
+const SWATCH_SIZE: f32 = 28.;
+/// hues in harmony on the 2d viz
+const HARMONY_COLOR: Color = Color::srgba(1., 1., 1., 0.8);
+/// distance between the 2d viz and the middle of the achromatic bar
+const ACHROMATIC_BAR_GAP: f32 = 12.;
+
+/// initial position of the 3d viz camera, also used when resetting the view
+pub fn viz3d_cam_transform() -> Transform {
+    Transform::from_translation(COLOR_3D_VIZ_COORD + Vec3::new(-2., 2., -2.))
+        .looking_at(COLOR_3D_VIZ_COORD, Vec3::Y)
+}
 
 pub fn draw_scene<A: CSpaceProvider>(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut images: ResMut<Assets<Image>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    query: Query<(Entity, &ImageLoader)>,
+    query: Query<(Entity, &ImageLoader, Option<&MaskLoader>)>,
     mut provider: ResMut<A>,
     image_filters: ResMut<Assets<A::FilterMaterial>>,
     mut viz2d_materials: ResMut<Assets<A::Viz2dMaterial>>,
     mut viz3d_materials: ResMut<Assets<A::Viz3dMaterial>>,
-    color_materials: ResMut<Assets<ColorMaterial>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    scaling: Res<Scaling>,
+    opts: Res<Cli>,
+    roi: Res<Roi>,
+    source: Option<Res<SourceProfile>>,
+    exposure: Res<Exposure>,
+    simulation: Res<Simulation>,
 ) {
     if query.is_empty() {
         // image already loaded
         return;
     }
 
-    let (entity, loader) = query.single().unwrap();
+    let (entity, loader, mask_loader) = query.single().unwrap();
     let load_state = asset_server.get_load_state(&loader.0);
 
-    if let (Some(LoadState::Loaded), Some(image)) = (load_state, images.get_mut(&loader.0)) {
+    // e.g. a file that doesn't decode, there would be nothing to show
+    if let Some(LoadState::Failed(e)) = &load_state {
+        let file = opts.file.as_deref().unwrap_or_default();
+        exit_with_error(format!("Cannot load image '{}': {}", file, e));
+    }
+    if let Some(m) = mask_loader
+        && let Some(LoadState::Failed(e)) = asset_server.get_load_state(&m.0)
+    {
+        exit_with_error(format!("Cannot load mask: {}", e));
+    }
+
+    // the mask is needed by every statistic, wait for both. Frames are added by setup_scene_pre
+    // rather than loaded, so look for the images instead of their load state
+    if !images.contains(&loader.0) || mask_loader.is_some_and(|m| !images.contains(&m.0)) {
+        return;
+    }
+    let mask = mask_loader.map(|m| Mask::new(images.get(&m.0).unwrap(), opts.invert_mask));
+    let mask = mask.transpose().unwrap_or_else(|e| exit_with_error(e));
+
+    let Some(image) = images.get_mut(&loader.0) else {
+        return;
+    };
+    let Prepared {
+        analyzed,
+        hdr,
+        outside,
+    } = prepare(&loader.0, image, source.as_deref(), *exposure, *simulation);
+    // e.g. a compressed texture, none of its pixels could be analyzed
+    if let Err(e) = analyzed.get_color_at(0, 0) {
+        let file = opts.file.as_deref().unwrap_or_default();
+        exit_with_error(format!("Cannot analyze image '{}': {}", file, e));
+    }
+    let gamut = source.zip(outside).map(|(source, outside)| {
+        let note = gamut_note(&source, &outside);
+        (gamut_overlay(&outside, analyzed.size()), note)
+    });
+    let gamut = gamut.map(|(overlay, note)| (images.add(overlay), note));
+    let hdr_note = hdr.as_ref().map(|hdr| hdr.note(*exposure));
+    if let Some(hdr) = hdr {
+        commands.insert_resource(hdr);
+    }
+
+    if let Some(image) = images.get_mut(&loader.0) {
+        if let Some(mask) = &mask
+            && mask.size() != image.size()
+        {
+            exit_with_error(format!(
+                "mask is {}x{} but the image is {}x{}",
+                mask.size().x,
+                mask.size().y,
+                image.width(),
+                image.height()
+            ));
+        }
+
         // delete marker entity
         commands.entity(entity).despawn();
-        // add image to background entity
-        commands.insert_resource(Background(image.clone()));
 
         // display image
-        spawn_image::<A>(image, &mut commands, &mut meshes, &provider, image_filters);
+        spawn_image::<A>(
+            loader.0.clone(),
+            image,
+            gamut,
+            hdr_note,
+            simulation.note(),
+            &mut commands,
+            &mut meshes,
+            &mut color_materials,
+            &provider,
+            image_filters,
+        );
 
         // display 2d viz
         spawn_2dviz_square::<A>(&mut commands, &mut meshes, &mut viz2d_materials);
 
+        // start with the coarsest sample, refine_histogram takes it from there
+        let sampler = Sampler::new(
+            &analyzed,
+            opts.sampling,
+            opts.max_pixels.map(|m| m as usize),
+            opts.sample_seed,
+            opts.alpha,
+            mask,
+        );
+        let mut histogram = Histogram::new(provider.as_ref(), &analyzed, &sampler, 0, &roi);
+        commands.insert_resource(sampler);
+
         // spawn rectangles that would generate the histogram shape
         // by covering extra parts
-        spawn_histogram_covering(
-            &mut provider,
-            image,
+        spawn_histogram_covering::<A>(
+            &histogram,
+            &scaling,
             &mut commands,
             &mut meshes,
-            color_materials,
+            &mut color_materials,
         );
+        let (mesh, share) = slice_mesh(provider.as_mut(), &analyzed, &scaling, &mut histogram);
 
         commands.spawn((
-            Text2d::new(format!("{}", provider.current())),
+            Text2d::new(banner_text(provider.as_ref(), &scaling, &histogram, share)),
+            // below the achromatic bar
+            Anchor::TopCenter,
             Transform::from_translation(
-                COLOR_2D_VIZ_COORD + Vec3::new(0., -COLOR_2D_VIZ_SIZE * 0.6, 2.),
+                COLOR_2D_VIZ_COORD
+                    + Vec3::new(0., -COLOR_2D_VIZ_SIZE / 2. - ACHROMATIC_BAR_GAP * 2., 2.),
             ),
+            Banner,
         ));
+        commands.insert_resource(histogram);
+        // the pixels as analyzed
+        commands.insert_resource(Background(analyzed));
 
         commands.spawn((
             (
-                Mesh3d(meshes.add(provider.create_mesh(image))),
+                Mesh3d(meshes.add(mesh)),
                 MeshMaterial3d(viz3d_materials.add(provider.get_viz3d_material())),
                 Transform::from_translation(COLOR_3D_VIZ_COORD),
             ),
@@ -128,18 +361,106 @@ pub fn draw_scene<A: CSpaceProvider>(
                     order: 3,
                     ..default()
                 },
-                Transform::from_translation(COLOR_3D_VIZ_COORD + Vec3::new(-2., 2., -2.))
-                    .looking_at(COLOR_3D_VIZ_COORD, Vec3::Y),
+                viz3d_cam_transform(),
             ),
             (CamViewPort::Viz3d, MeshControlConf::default()),
         ));
     }
 }
 
+/// An image brought into sRGB and within [0,1], see prepare
+struct Prepared {
+    /// the pixels as analyzed
+    analyzed: Image,
+    hdr: Option<HdrSource>,
+    /// which pixels were outside of sRGB, when converted from a color profile
+    outside: Option<Vec<bool>>,
+}
+
+/// Everything is analyzed in sRGB and within [0,1], so convert before anything looks at the
+/// pixels. 16-bit and floating-point images are kept aside to expose them again, and shown as
+/// 8-bit. A simulated deficiency is only shown by the filter, unless it is analyzed
+fn prepare(
+    texture: &Handle<Image>,
+    image: &mut Image,
+    source: Option<&SourceProfile>,
+    exposure: Exposure,
+    simulation: Simulation,
+) -> Prepared {
+    let hdr = HdrSource::new(texture.clone(), image, source.map(|s| &s.profile));
+    let (analyzed, outside) = match &hdr {
+        Some(hdr) => {
+            let (analyzed, shown) = hdr.expose(exposure);
+            *image = shown;
+            (analyzed, hdr.outside.clone())
+        }
+        None => {
+            let outside = source.and_then(|s| to_srgb(image, &s.profile));
+            (image.clone(), outside)
+        }
+    };
+    Prepared {
+        analyzed: simulation.apply(analyzed),
+        hdr,
+        outside,
+    }
+}
+
+/// where the colors came from and how many didn't fit in sRGB
+fn gamut_note(source: &SourceProfile, outside: &[bool]) -> String {
+    let share = outside.iter().filter(|o| **o).count() as f32 / outside.len().max(1) as f32;
+    format!("{}, {:.2}% outside sRGB", source.label, share * 100.)
+}
+
+/// Convert the pixels from the color space the image declares, returning which of them were
+/// outside of sRGB. None if the texture format isn't one that can be converted
+fn to_srgb(image: &mut Image, profile: &Profile) -> Option<Vec<bool>> {
+    let format = image.texture_descriptor.format;
+    if format != TextureFormat::Rgba8UnormSrgb {
+        eprintln!(
+            "warning: cannot convert {:?} textures from their color profile, taking the image as sRGB",
+            format
+        );
+        return None;
+    }
+    Some(profile.convert_rgba8(image.data.as_mut()?))
+}
+
+/// marks the pixels that were outside of sRGB, transparent elsewhere
+fn gamut_overlay(outside: &[bool], size: UVec2) -> Image {
+    let data = outside
+        .iter()
+        .flat_map(|o| if *o { GAMUT_COLOR } else { [0; 4] })
+        .collect();
+    overlay_image(data, size)
+}
+
+/// an sRGB texture drawn over the image, texel for texel
+fn overlay_image(data: Vec<u8>, size: UVec2) -> Image {
+    let mut overlay = Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    overlay.sampler = ImageSampler::nearest();
+    overlay
+}
+
 fn spawn_image<A: CSpaceProvider>(
+    texture: Handle<Image>,
     image: &mut Image,
+    gamut: Option<(Handle<Image>, String)>,
+    hdr_note: Option<String>,
+    cvd_note: String,
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
+    color_materials: &mut Assets<ColorMaterial>,
     provider: &ResMut<A>,
     mut image_filters: ResMut<Assets<A::FilterMaterial>>,
 ) {
@@ -147,35 +468,94 @@ fn spawn_image<A: CSpaceProvider>(
     image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
         address_mode_u: AddressMode::ClampToEdge.into(),
         address_mode_v: AddressMode::ClampToEdge.into(),
-        mag_filter: FilterMode::Linear.into(),
+        // show crisp pixels when zoomed in
+        mag_filter: FilterMode::Nearest.into(),
         min_filter: FilterMode::Linear.into(),
         mipmap_filter: FilterMode::Linear.into(),
         ..default()
     });
 
-    let aspect_ratio =
-        image.texture_descriptor.size.width as f32 / image.texture_descriptor.size.height as f32;
+    let texels = image.size_f32();
+    let size = canvas_size(texels);
 
     // spawn a square the has the right dimensions and use the image as material
     commands.spawn((
         (
-            Mesh2d(meshes.add(Rectangle::new(IMG_BASE_SIZE * aspect_ratio, IMG_BASE_SIZE))),
+            Mesh2d(meshes.add(Rectangle::from_size(size))),
             MeshMaterial2d(image_filters.add(provider.get_filter())),
         ),
-        ImageCanvas,
+        ImageCanvas(texture),
     ));
+    if let Some((overlay, _)) = &gamut {
+        commands.spawn((
+            Mesh2d(meshes.add(Rectangle::from_size(size))),
+            MeshMaterial2d(color_materials.add(overlay.clone())),
+            // above the image
+            Transform::from_xyz(0., 0., 1.),
+            Visibility::Hidden,
+            GamutOverlay,
+        ));
+    }
 
     // camera
+    let camera = commands
+        .spawn((
+            (
+                Camera2d,
+                Camera {
+                    order: 1,
+                    ..default()
+                },
+            ),
+            (CamViewPort::ImageFilter, ImageViewConf::new(size, texels)),
+        ))
+        .id();
+
+    // current select tool, in the corner of the image view
     commands.spawn((
-        (
-            Camera2d,
-            Camera {
-                order: 1,
+        Text::default(),
+        TextFont::from_font_size(16.),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.),
+            bottom: Val::Px(8.),
+            ..default()
+        },
+        UiTargetCamera(camera),
+        SelectionHint,
+    ));
+
+    // how bright the image is and the color space it was converted from, both change with the
+    // frame and the first with the exposure, then the simulated deficiency, the harmony, which
+    // apply_harmonies fills in, and the quantization, which apply_quantization fills in
+    let font = TextFont::from_font_size(16.);
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(8.),
+                top: Val::Px(8.),
+                flex_direction: FlexDirection::Column,
                 ..default()
             },
-        ),
-        CamViewPort::ImageFilter,
-    ));
+            UiTargetCamera(camera),
+        ))
+        .with_children(|notes| {
+            if let Some(hdr_note) = hdr_note {
+                notes.spawn((Text::new(hdr_note), font.clone(), HdrNote));
+            }
+            if let Some((_, gamut_note)) = gamut {
+                notes.spawn((Text::new(gamut_note), font.clone(), GamutNote));
+            }
+            notes.spawn((Text::new(cvd_note), font.clone(), CvdNote));
+            notes.spawn((Text::default(), font.clone(), HarmonyNote));
+            notes.spawn((Text::default(), font, QuantizeNote));
+        });
+}
+
+/// the image is shown at the same height whatever its resolution
+fn canvas_size(texels: Vec2) -> Vec2 {
+    Vec2::new(IMG_BASE_SIZE * texels.x / texels.y, IMG_BASE_SIZE)
 }
 
 fn spawn_2dviz_square<A: CSpaceProvider>(
@@ -209,35 +589,50 @@ fn spawn_2dviz_square<A: CSpaceProvider>(
 }
 
 fn spawn_histogram_covering<A: CSpaceProvider>(
-    provider: &mut ResMut<A>,
-    image: &Image,
+    histogram: &Histogram,
+    scaling: &Scaling,
     commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    meshes: &mut Assets<Mesh>,
+    color_materials: &mut Assets<ColorMaterial>,
 ) {
-    let mut data = provider.histogram_data(image);
-    // normalize
-    let max = data
+    // normalize, the achromatic bucket is compared against the same bins
+    let mut ratios: Vec<f32> = histogram.data.iter().map(|(_, y)| *y).collect();
+    ratios.push(histogram.achromatic);
+    let scale = scaling.scaler(&ratios);
+    let data: Vec<(f32, f32)> = histogram
+        .data
         .iter()
-        .max_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
-        .unwrap()
-        .1;
-    data.iter_mut().for_each(|(_, y)| *y /= max);
+        .map(|(x, y)| (*x, scale(*y)))
+        .collect();
+
+    if histogram.achromatic > 0. {
+        // its own bar under the 2d viz, growing from the left
+        let width = scale(histogram.achromatic) * COLOR_2D_VIZ_SIZE;
+        commands.spawn((
+            Mesh2d(meshes.add(Mesh::from(Rectangle::new(width, ACHROMATIC_BAR_HEIGHT)))),
+            MeshMaterial2d(color_materials.add(Color::srgb_u8(128, 128, 128))),
+            Transform::from_translation(
+                COLOR_2D_VIZ_COORD
+                    + Vec3::new(
+                        (width - COLOR_2D_VIZ_SIZE) / 2.,
+                        -COLOR_2D_VIZ_SIZE / 2. - ACHROMATIC_BAR_GAP,
+                        1.,
+                    ),
+            ),
+            HistogramCover,
+        ));
+    }
 
     let mut x = A::MIN;
     let mut iter = data.iter().peekable();
     while x <= A::MAX {
         // data is in ascending order, so just iter through
         let ratio = match iter.peek() {
-            Some((y, z)) => {
-                if *y == x {
-                    iter.next();
-                    *z
-                } else {
-                    0.
-                }
+            Some((y, z)) if *y == x => {
+                iter.next();
+                *z
             }
-            None => 0.,
+            _ => 0.,
         };
         commands.spawn((
             Mesh2d(meshes.add(Mesh::from(Rectangle::new(A::DELTA / A::MAX, 1. - ratio)))),
@@ -253,35 +648,861 @@ fn spawn_histogram_covering<A: CSpaceProvider>(
                             + Vec3::Y * (ratio * COLOR_2D_VIZ_SIZE / 2.),
             )
             .with_scale(Vec3::splat(COLOR_2D_VIZ_SIZE)),
+            HistogramCover,
         ));
         x += A::DELTA;
     }
 }
 
-pub const IMG_VIEW_W_RATIO: f32 = 0.8;
-pub const VIZ3D_H_RATIO: f32 = 0.5;
-pub fn set_viewports(window: Single<&Window>, mut query: Query<(&CamViewPort, &mut Camera)>) {
+fn banner_text<A: CSpaceProvider>(
+    provider: &A,
+    scaling: &Scaling,
+    histogram: &Histogram,
+    share: f32,
+) -> String {
+    let mut text = format!(
+        "{}\n{} / {}\n{:.2}% of pixels",
+        provider.current(),
+        scaling.mode,
+        scaling.normalization,
+        share * 100.
+    );
+    if histogram.achromatic > 0. {
+        text += &format!("\n{:.2}% achromatic", histogram.achromatic * 100.);
+    }
+    let sampled = histogram.weights.sampled_ratio();
+    if sampled < 1. {
+        text += &format!("\n{:.2}% of pixels sampled", sampled * 100.);
+    }
+    if histogram.refining {
+        text += "\nrefining...";
+    }
+    text
+}
+
+/// mesh of the current slice, and the share of all pixels that are in the slice
+fn slice_mesh<A: CSpaceProvider>(
+    provider: &mut A,
+    image: &Image,
+    scaling: &Scaling,
+    histogram: &mut Histogram,
+) -> (Mesh, f32) {
+    let stats = provider.slice_stats(image, &histogram.weights);
+    let counts: Vec<f32> = stats.values().map(|c| *c as f32).collect();
+    let scale = match scaling.normalization {
+        Normalization::Slice => scaling.scaler(&counts),
+        Normalization::Global => scaling.scaler(
+            histogram
+                .global_bins
+                .get_or_insert_with(|| provider.global_bins(image, &histogram.weights)),
+        ),
+        Normalization::Total => scaling.scaler(&[1., histogram.weights.total() as f32]),
+    };
+    let share = (stats.values().sum::<f64>() / histogram.weights.total()) as f32;
+    (provider.create_mesh(&stats, scale), share)
+}
+
+/// show another frame, prepared as draw_scene does the first one, refine_histogram starts over
+/// from the coarsest sample then
+pub fn show_frame<A: CSpaceProvider>(
+    mut commands: Commands,
+    frames: Option<Res<Frames>>,
+    img: Option<ResMut<Background>>,
+    mut images: ResMut<Assets<Image>>,
+    mut p: ResMut<A>,
+    source: Option<Res<SourceProfile>>,
+    exposure: Res<Exposure>,
+    simulation: Res<Simulation>,
+    overlay: Query<&MeshMaterial2d<ColorMaterial>, With<GamutOverlay>>,
+    color_materials: Res<Assets<ColorMaterial>>,
+    mut hdr_text: Query<&mut Text, (With<HdrNote>, Without<GamutNote>)>,
+    mut gamut_text: Query<&mut Text, With<GamutNote>>,
+) {
+    let (Some(frames), Some(mut img)) = (frames, img) else {
+        return;
+    };
+    if !frames.is_changed() {
+        return;
+    }
+    let Some(image) = images.get_mut(&frames.texture) else {
+        return;
+    };
+    let sampler = image.sampler.clone();
+    *image = frames.images[frames.current].clone();
+    let prepared = prepare(
+        &frames.texture,
+        image,
+        source.as_deref(),
+        *exposure,
+        *simulation,
+    );
+    image.sampler = sampler;
+
+    img.0 = prepared.analyzed;
+    if let Some(hdr) = prepared.hdr {
+        for mut text in &mut hdr_text {
+            text.0 = hdr.note(*exposure);
+        }
+        commands.insert_resource(hdr);
+    }
+    if let (Some(source), Some(outside)) = (source, prepared.outside) {
+        for mut text in &mut gamut_text {
+            text.0 = gamut_note(&source, &outside);
+        }
+        let texture = overlay
+            .iter()
+            .filter_map(|m| color_materials.get(&m.0)?.texture.clone())
+            .next();
+        if let Some(texture) = texture.and_then(|t| images.get_mut(&t)) {
+            texture.data = gamut_overlay(&outside, img.0.size()).data;
+        }
+    }
+    // the colors changed, so did where the pixels fall
+    p.clear_cache();
+}
+
+/// build the timeline of an animation or sequence once the image view is there, again when the
+/// exposure changes, and follow the current frame
+pub fn draw_timeline<A: CSpaceProvider>(
+    mut commands: Commands,
+    frames: Option<Res<Frames>>,
+    exposure: Res<Exposure>,
+    opts: Res<Cli>,
+    source: Option<Res<SourceProfile>>,
+    mut images: ResMut<Assets<Image>>,
+    camera: Query<Entity, With<ImageViewConf>>,
+    mut strip: Query<&mut ImageNode, With<TimelineStrip>>,
+    mut marker: Query<&mut Node, With<TimelineMarker>>,
+    mut label: Query<&mut Text, With<TimelineLabel>>,
+) {
+    let (Some(frames), Ok(camera)) = (frames, camera.single()) else {
+        return;
+    };
+    let mut build = || {
+        let mut timeline = timeline(
+            &frames.images,
+            A::SPACE,
+            opts.achromatic / 100.,
+            source.as_deref().map(|s| &s.profile),
+            *exposure,
+        );
+        // one column per frame, crisp
+        timeline.sampler = ImageSampler::nearest();
+        images.add(timeline)
+    };
+    match strip.single_mut() {
+        Err(_) => spawn_timeline(&mut commands, camera, build(), &frames),
+        Ok(mut node) if exposure.is_changed() => node.image = build(),
+        Ok(_) => {}
+    }
+    if frames.is_changed() {
+        for mut node in &mut marker {
+            node.left = Val::Percent(marker_position(&frames));
+        }
+        for mut text in &mut label {
+            text.0 = frames.label();
+        }
+    }
+}
+
+/// middle of the current frame's column, in percent of the strip
+fn marker_position(frames: &Frames) -> f32 {
+    (frames.current as f32 + 0.5) / frames.images.len() as f32 * 100.
+}
+
+/// the frame label and the strip along the bottom of the image view, above the select tool
+fn spawn_timeline(
+    commands: &mut Commands,
+    camera: Entity,
+    timeline: Handle<Image>,
+    frames: &Frames,
+) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(8.),
+                right: Val::Px(8.),
+                bottom: Val::Px(32.),
+                height: Val::Px(TIMELINE_HEIGHT),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            UiTargetCamera(camera),
+            Timeline,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(frames.label()),
+                TextFont::from_font_size(16.),
+                TimelineLabel,
+            ));
+            parent
+                .spawn((
+                    ImageNode::new(timeline),
+                    Node {
+                        flex_grow: 1.,
+                        ..default()
+                    },
+                    BackgroundColor(TIMELINE_BACKGROUND),
+                    Interaction::default(),
+                    RelativeCursorPosition::default(),
+                    TimelineStrip,
+                ))
+                .with_children(|strip| {
+                    strip.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            left: Val::Percent(marker_position(frames)),
+                            width: Val::Px(2.),
+                            height: Val::Percent(100.),
+                            ..default()
+                        },
+                        BackgroundColor(DRAWING_COLOR),
+                        TimelineMarker,
+                    ));
+                });
+        });
+}
+
+/// rebuild the contrast panel when a swatch is picked or they are cleared, hidden below two
+pub fn draw_contrast(
+    mut commands: Commands,
+    swatches: Res<Swatches>,
+    camera: Query<Entity, With<ImageViewConf>>,
+    panel: Query<Entity, With<ContrastPanel>>,
+) {
+    if !swatches.is_changed() {
+        return;
+    }
+    for entity in &panel {
+        commands.entity(entity).despawn();
+    }
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+    if swatches.0.len() >= 2 {
+        spawn_contrast(&mut commands, camera, &swatches.0);
+    }
+}
+
+/// A matrix with the text colors in the rows and the backgrounds in the columns. Each cell has the
+/// WCAG ratio and the APCA Lc, in red when too low for body text, and then the OKHSL lightness the
+/// text color would need
+fn spawn_contrast(commands: &mut Commands, camera: Entity, swatches: &[Srgb]) {
+    let font = TextFont::from_font_size(14.);
+    let swatch = |parent: &mut ChildSpawnerCommands, i: usize| {
+        let color = swatches[i];
+        // whichever is easier to read on it
+        let black = Srgb::new(0., 0., 0.);
+        let white = Srgb::new(1., 1., 1.);
+        let label = if contrast::wcag_ratio(black, color) > contrast::wcag_ratio(white, color) {
+            Color::BLACK
+        } else {
+            Color::WHITE
+        };
+        parent
+            .spawn((
+                Node {
+                    width: Val::Px(SWATCH_SIZE),
+                    height: Val::Px(SWATCH_SIZE),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(color.red, color.green, color.blue)),
+            ))
+            .with_child((
+                Text::new((i + 1).to_string()),
+                font.clone(),
+                TextColor(label),
+            ));
+    };
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(8.),
+                top: Val::Px(8.),
+                display: Display::Grid,
+                grid_template_columns: RepeatedGridTrack::auto(swatches.len() as u16 + 1),
+                align_items: AlignItems::Center,
+                justify_items: JustifyItems::Center,
+                row_gap: Val::Px(4.),
+                column_gap: Val::Px(8.),
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            BackgroundColor(TIMELINE_BACKGROUND),
+            UiTargetCamera(camera),
+            ContrastPanel,
+        ))
+        .with_children(|panel| {
+            // backgrounds along the top
+            panel.spawn(Node::default());
+            for j in 0..swatches.len() {
+                swatch(panel, j);
+            }
+            for (i, text) in swatches.iter().enumerate() {
+                swatch(panel, i);
+                for (j, background) in swatches.iter().enumerate() {
+                    if i == j {
+                        panel.spawn(Node::default());
+                        continue;
+                    }
+                    panel
+                        .spawn((Text::default(), font.clone()))
+                        .with_children(|cell| {
+                            for (line, color) in contrast_cell(*text, *background) {
+                                cell.spawn((TextSpan::new(line), font.clone(), color));
+                            }
+                        });
+                }
+            }
+            panel.spawn((
+                Text::new(format!(
+                    "rows on columns, WCAG {}:1 and APCA Lc {} for body text, \
+                     L: OKHSL lightness of the row that would pass",
+                    contrast::WCAG_AA,
+                    contrast::APCA_BODY
+                )),
+                TextFont::from_font_size(12.),
+                Node {
+                    grid_column: GridPlacement::span(swatches.len() as u16 + 1),
+                    max_width: Val::Px(SWATCH_SIZE * 2. * (swatches.len() + 1) as f32),
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// the lines of a cell of the contrast panel and their colors
+fn contrast_cell(text: Srgb, background: Srgb) -> Vec<(String, TextColor)> {
+    let wcag = contrast::wcag_ratio(text, background);
+    let apca = contrast::apca(text, background);
+    let color = |pass: bool| TextColor(if pass { Color::WHITE } else { FAIL_COLOR });
+    let mut lines = vec![
+        (format!("{:.1}:1", wcag), color(wcag >= contrast::WCAG_AA)),
+        (
+            format!("\nLc {:.0}", apca),
+            color(apca.abs() >= contrast::APCA_BODY),
+        ),
+    ];
+    if !contrast::legible(text, background) {
+        let fix = match contrast::fix_lightness(text, background) {
+            Some(l) => format!("\nL {:.2}", l),
+            None => "\nno L".to_string(),
+        };
+        lines.push((fix, color(false)));
+    }
+    lines
+}
+
+/// make the terminal scheme from the sample when it is shown, and again when the sample changes
+pub fn make_scheme(
+    mut terminal: ResMut<TerminalScheme>,
+    img: Option<Res<Background>>,
+    histogram: Option<Res<Histogram>>,
+    mut was_shown: Local<bool>,
+) {
+    let just_shown = terminal.shown && !std::mem::replace(&mut *was_shown, terminal.shown);
+    let (Some(img), Some(histogram)) = (img, histogram) else {
+        return;
+    };
+    if !terminal.shown || (!just_shown && !histogram.is_changed()) {
+        return;
+    }
+    let colors = histogram
+        .weights
+        .iter()
+        .map(|(i, j, weight)| (srgb_at(&img.0, i, j), weight));
+    terminal.scheme = Scheme::from_weighted(colors, contrast::WCAG_AA);
+}
+
+/// rebuild the scheme panel when the scheme is made again, shown or hidden
+pub fn draw_scheme(
+    mut commands: Commands,
+    terminal: Res<TerminalScheme>,
+    frames: Option<Res<Frames>>,
+    camera: Query<Entity, With<ImageViewConf>>,
+    panel: Query<Entity, With<SchemePanel>>,
+) {
+    if !terminal.is_changed() {
+        return;
+    }
+    for entity in &panel {
+        commands.entity(entity).despawn();
+    }
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+    if let Some(scheme) = terminal.scheme.filter(|_| terminal.shown) {
+        spawn_scheme(&mut commands, camera, &scheme, frames.is_some());
+    }
+}
+
+/// A terminal with the scheme: the normal colors and the bright ones, each named in its color on
+/// the background, above the swatches
+fn spawn_scheme(commands: &mut Commands, camera: Entity, scheme: &Scheme, timeline: bool) {
+    let font = TextFont::from_font_size(12.);
+    let color = |c: Srgb| Color::srgb(c.red, c.green, c.blue);
+    let row = |panel: &mut ChildSpawnerCommands, colors: &[Srgb], names: &[&str]| {
+        panel
+            .spawn(Node {
+                column_gap: Val::Px(8.),
+                ..default()
+            })
+            .with_children(|row| {
+                for (c, name) in colors.iter().zip(names) {
+                    row.spawn(Node {
+                        flex_direction: FlexDirection::Column,
+                        width: Val::Px(SWATCH_SIZE * 2.),
+                        row_gap: Val::Px(2.),
+                        ..default()
+                    })
+                    .with_children(|slot| {
+                        let name = name.trim_start_matches("bright ");
+                        slot.spawn((Text::new(name), font.clone(), TextColor(color(*c))));
+                        slot.spawn((
+                            Node {
+                                height: Val::Px(SWATCH_SIZE / 2.),
+                                ..default()
+                            },
+                            BackgroundColor(color(*c)),
+                        ));
+                    });
+                }
+            });
+    };
+
+    // above the timeline, if any
+    let bottom = if timeline { TIMELINE_HEIGHT + 8. } else { 8. };
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(8.),
+                bottom: Val::Px(bottom),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.),
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            BackgroundColor(color(scheme.background)),
+            UiTargetCamera(camera),
+            SchemePanel,
+        ))
+        .with_children(|panel| {
+            let theme = if scheme.is_dark() { "dark" } else { "light" };
+            panel.spawn((
+                Text::new(format!("{} terminal scheme", theme)),
+                font.clone(),
+                TextColor(color(scheme.foreground)),
+            ));
+            row(panel, &scheme.ansi[..8], &ANSI_NAMES[..8]);
+            row(panel, &scheme.ansi[8..], &ANSI_NAMES[8..]);
+        });
+}
+
+/// show a pasted image instead, which may have another size. It is 8-bit sRGB and a single frame,
+/// so everything about profiles, exposure and frames goes away
+pub fn replace_image<A: CSpaceProvider>(
+    mut commands: Commands,
+    decoded: Option<Res<Decoded>>,
+    img: Option<ResMut<Background>>,
+    sampler: Option<ResMut<Sampler>>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut p: ResMut<A>,
+    mut roi: ResMut<Roi>,
+    opts: Res<Cli>,
+    exposure: Res<Exposure>,
+    simulation: Res<Simulation>,
+    mut canvas: Query<(&ImageCanvas, &mut Mesh2d)>,
+    mut camera: Query<&mut ImageViewConf>,
+    gone: Query<
+        Entity,
+        Or<(
+            With<GamutOverlay>,
+            With<HdrNote>,
+            With<GamutNote>,
+            With<Timeline>,
+        )>,
+    >,
+) {
+    let (Some(decoded), Some(mut img), Some(mut sampler)) = (decoded, img, sampler) else {
+        return;
+    };
+    commands.remove_resource::<Decoded>();
+    let Ok((canvas, mut mesh)) = canvas.single_mut() else {
+        return;
+    };
+    let texels = decoded.0.size();
+    if let Some(mask) = &sampler.mask
+        && mask.size() != texels
+    {
+        eprintln!(
+            "warning: the pasted image is {}x{} but the mask is {}x{}, keeping the current image",
+            texels.x,
+            texels.y,
+            mask.size().x,
+            mask.size().y
+        );
+        return;
+    }
+    let Some(image) = images.get_mut(&canvas.0) else {
+        return;
+    };
+    let image_sampler = image.sampler.clone();
+    *image = decoded.0.clone();
+    let prepared = prepare(&canvas.0, image, None, *exposure, *simulation);
+    image.sampler = image_sampler;
+
+    commands.remove_resource::<SourceProfile>();
+    commands.remove_resource::<HdrSource>();
+    commands.remove_resource::<Frames>();
+    for entity in &gone {
+        commands.entity(entity).despawn();
+    }
+
+    let size = canvas_size(texels.as_vec2());
+    mesh.0 = meshes.add(Rectangle::from_size(size));
+    for mut conf in &mut camera {
+        *conf = ImageViewConf::new(size, texels.as_vec2());
+    }
+    // a region of the old image means nothing on the new one
+    if roi.0.is_some() {
+        roi.0 = None;
+    }
+    let mask = sampler.mask.take();
+    *sampler = Sampler::new(
+        &prepared.analyzed,
+        opts.sampling,
+        opts.max_pixels.map(|m| m as usize),
+        opts.sample_seed,
+        opts.alpha,
+        mask,
+    );
+    img.0 = prepared.analyzed;
+    p.clear_cache();
+}
+
+/// show the image as seen with the simulated deficiency, and analyze it that way when asked to,
+/// refine_histogram starts over from the coarsest sample then
+pub fn apply_simulation<A: CSpaceProvider>(
+    simulation: Res<Simulation>,
+    exposure: Res<Exposure>,
+    hdr: Option<Res<HdrSource>>,
+    img: Option<ResMut<Background>>,
+    images: Res<Assets<Image>>,
+    canvas: Query<&ImageCanvas>,
+    mut p: ResMut<A>,
+    mut note: Query<&mut Text, With<CvdNote>>,
+    // whether the pixels as analyzed are simulated
+    mut analyzed: Local<bool>,
+) {
+    if !simulation.is_changed() {
+        return;
+    }
+    p.set_simulation(*simulation);
+    for mut text in &mut note {
+        text.0 = simulation.note();
+    }
+    // the filter alone shows the deficiency, unless it is analyzed now or was until now. Before
+    // the image is drawn, draw_scene takes the simulation as it is
+    let was_analyzed = std::mem::replace(&mut *analyzed, simulation.analysis);
+    let (Some(mut img), Ok(canvas)) = (img, canvas.single()) else {
+        return;
+    };
+    if !simulation.analysis && !was_analyzed {
+        return;
+    }
+    // what the texture shows is what prepare analyzed, 16-bit and floating-point images are shown
+    // as 8-bit though
+    let unsimulated = match hdr {
+        Some(hdr) => hdr.expose(*exposure).0,
+        None => match images.get(&canvas.0) {
+            Some(image) => image.clone(),
+            None => return,
+        },
+    };
+    img.0 = simulation.apply(unsimulated);
+    // the colors changed, so did where the pixels fall
+    p.clear_cache();
+}
+
+/// mark the hues in harmony, with the dominant hue following the sample, the frame or a paste
+pub fn apply_harmonies<A: CSpaceProvider>(
+    harmonies: Res<Harmonies>,
+    histogram: Option<Res<Histogram>>,
+    mut p: ResMut<A>,
+    mut note: Query<(&mut Text, Ref<HarmonyNote>)>,
+    // what the provider was last given
+    mut applied: Local<Option<(Harmonies, Option<f32>)>>,
+) {
+    let dominant = histogram
+        .filter(|_| harmonies.dominant)
+        .and_then(|histogram| dominant_hue(&histogram.data, A::SPACE));
+    if *applied != Some((*harmonies, dominant)) {
+        p.set_harmonies(*harmonies, dominant);
+        *applied = Some((*harmonies, dominant));
+    }
+    // the note also follows the current hue
+    if !p.is_changed() && !note.iter().any(|(_, n)| n.is_added()) {
+        return;
+    }
+    for (mut text, _) in &mut note {
+        text.0 = harmonies.note(A::SPACE, p.current(), dominant);
+    }
+}
+
+/// mark the hues in harmony on the 2d viz, over the histogram
+pub fn draw_harmonies<A: CSpaceProvider>(
+    mut gizmos: Gizmos,
+    harmonies: Res<Harmonies>,
+    histogram: Option<Res<Histogram>>,
+    p: Res<A>,
+) {
+    let Some(histogram) = histogram.filter(|_| A::SPACE.by_hue()) else {
+        return;
+    };
+    let dominant = dominant_hue(&histogram.data, A::SPACE);
+    for hue in harmonies.hues(p.current(), dominant) {
+        let x = COLOR_2D_VIZ_COORD.x + (hue / 360. - 0.5) * COLOR_2D_VIZ_SIZE;
+        let (bottom, top) = (-COLOR_2D_VIZ_SIZE / 2., COLOR_2D_VIZ_SIZE / 2.);
+        gizmos.line_2d(
+            Vec2::new(x, COLOR_2D_VIZ_COORD.y + bottom),
+            Vec2::new(x, COLOR_2D_VIZ_COORD.y + top),
+            HARMONY_COLOR,
+        );
+    }
+}
+
+/// Show the image quantized to the palette, or its error, over it once toggled, again when the
+/// palette, the metric or the pixels change. The error of the sample goes in the note
+pub fn apply_quantization(
+    mut commands: Commands,
+    quantization: Res<Quantization>,
+    swatches: Res<Swatches>,
+    img: Option<Res<Background>>,
+    histogram: Option<Res<Histogram>>,
+    mut images: ResMut<Assets<Image>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    canvas: Query<&Mesh2d, With<ImageCanvas>>,
+    overlay: Query<Entity, With<QuantizeOverlay>>,
+    mut note: Query<&mut Text, With<QuantizeNote>>,
+    mut cache: Local<Option<NearestCache>>,
+) {
+    let (Some(img), Some(histogram), Ok(mesh)) = (img, histogram, canvas.single()) else {
+        return;
+    };
+    let palette = quantization.palette(&swatches);
+    let stale = !cache
+        .as_ref()
+        .is_some_and(|c| c.is_for(palette, quantization.metric));
+    if stale {
+        *cache = Some(NearestCache::new(palette, quantization.metric));
+    }
+    let cache = cache.as_mut().unwrap();
+
+    let redraw = stale || quantization.is_changed() || img.is_changed();
+    if redraw {
+        for entity in &overlay {
+            commands.entity(entity).despawn();
+        }
+        if let Some(view) = quantization.view.filter(|_| !palette.is_empty()) {
+            let shown = overlay_image(cache.render(&img.0, view), img.0.size());
+            commands.spawn((
+                Mesh2d(mesh.0.clone()),
+                MeshMaterial2d(color_materials.add(images.add(shown))),
+                // above the image, below the pixels outside of sRGB
+                Transform::from_xyz(0., 0., 0.5),
+                QuantizeOverlay,
+            ));
+        }
+    }
+    if !redraw && !histogram.is_changed() {
+        return;
+    }
+    let stats = quantization
+        .view
+        .and_then(|_| cache.stats(&img.0, &histogram.weights));
+    for mut text in &mut note {
+        text.0 = quantization.note(palette.len(), stats);
+    }
+}
+
+/// expose 16-bit and floating-point images again when the exposure changes, refine_histogram
+/// starts over from the coarsest sample then
+pub fn apply_exposure<A: CSpaceProvider>(
+    exposure: Res<Exposure>,
+    simulation: Res<Simulation>,
+    hdr: Option<Res<HdrSource>>,
+    img: Option<ResMut<Background>>,
+    mut images: ResMut<Assets<Image>>,
+    mut p: ResMut<A>,
+    mut note: Query<&mut Text, With<HdrNote>>,
+) {
+    let (Some(hdr), Some(mut img)) = (hdr, img) else {
+        return;
+    };
+    if !exposure.is_changed() {
+        return;
+    }
+    let (analyzed, shown) = hdr.expose(*exposure);
+    img.0 = simulation.apply(analyzed);
+    // same size and format, the sampler stays
+    if let Some(texture) = images.get_mut(&hdr.texture) {
+        texture.data = shown.data;
+    }
+    // the colors changed, so did where the pixels fall
+    p.clear_cache();
+    for mut text in &mut note {
+        text.0 = hdr.note(*exposure);
+    }
+}
+
+/// replace the histogram with the next finer sample, one level per frame, and start over from
+/// the coarsest one when the region of interest or the pixels change, e.g. with the exposure, the
+/// frame or a paste
+pub fn refine_histogram<A: CSpaceProvider>(
+    p: Res<A>,
+    sampler: Option<Res<Sampler>>,
+    img: Option<Res<Background>>,
+    histogram: Option<ResMut<Histogram>>,
+    roi: Res<Roi>,
+) {
+    let (Some(sampler), Some(img), Some(mut histogram)) = (sampler, img, histogram) else {
+        return;
+    };
+    let level = if roi.is_changed() || img.is_changed() {
+        0
+    } else if histogram.refining {
+        histogram.level + 1
+    } else {
+        return;
+    };
+    *histogram = Histogram::new(p.as_ref(), &img.0, &sampler, level, &roi);
+}
+
+/// outline the region of interest and the selection being drawn
+pub fn draw_selection(
+    mut gizmos: Gizmos,
+    camera: Query<&ImageViewConf>,
+    selection: Res<Selection>,
+    roi: Res<Roi>,
+    mut hint: Query<&mut Text, With<SelectionHint>>,
+) {
+    if selection.is_changed()
+        && let Ok(mut hint) = hint.single_mut()
+    {
+        hint.0 = selection
+            .tool
+            .map(|tool| format!("select: {}", tool))
+            .unwrap_or_default();
+    }
+
+    let Ok(conf) = camera.single() else {
+        return;
+    };
+    if let Some(region) = &roi.0 {
+        let outline = region.outline.iter().map(|p| conf.to_world(*p));
+        gizmos.linestrip_2d(outline.clone().chain(outline.take(1)), SELECTION_COLOR);
+    }
+    if !selection.drawing.is_empty() {
+        // a polygon follows the cursor until it is closed
+        let rubber_band = selection
+            .cursor
+            .filter(|_| selection.tool == Some(SelectTool::Polygon));
+        let drawing = selection.drawing.iter().chain(rubber_band.as_ref());
+        gizmos.linestrip_2d(drawing.map(|p| conf.to_world(*p)), DRAWING_COLOR);
+    }
+}
+
+/// rebuild everything that depends on the param, the scaling or the sample
+pub fn update_viz<A: CSpaceProvider>(
+    mut commands: Commands,
+    mut p: ResMut<A>,
+    scaling: Res<Scaling>,
+    img: Option<Res<Background>>,
+    histogram: Option<ResMut<Histogram>>,
+    // queries for entities that needs to be updated
+    mut img_canvas: Query<&mut MeshMaterial2d<A::FilterMaterial>, With<ImageCanvas>>,
+    mut viz2d_canvas: Query<&mut MeshMaterial2d<A::Viz2dMaterial>, With<Viz2DCanvas>>,
+    mut viz3d_mesh: Query<(&mut MeshMaterial3d<A::Viz3dMaterial>, &mut Mesh3d), With<Viz3DMesh>>,
+    mut banner: Query<&mut Text2d, With<Banner>>,
+    covers: Query<Entity, With<HistogramCover>>,
+    // entity managers
+    mut img_filters: ResMut<Assets<A::FilterMaterial>>,
+    mut viz2d_materials: ResMut<Assets<A::Viz2dMaterial>>,
+    mut viz3d_materials: ResMut<Assets<A::Viz3dMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let (Some(img), Some(mut histogram)) = (img, histogram) else {
+        return;
+    };
+    if !p.is_changed() && !scaling.is_changed() && !histogram.is_changed() {
+        return;
+    }
+
+    // apply change, original item substituted
+    if p.is_changed() {
+        // update image filter
+        img_canvas.single_mut().unwrap().0 = img_filters.add(p.get_filter());
+        // update viz2d current color indicator
+        viz2d_canvas.single_mut().unwrap().0 = viz2d_materials.add(p.get_viz2d_material());
+        // update viz3d material
+        viz3d_mesh.single_mut().unwrap().0.0 = viz3d_materials.add(p.get_viz3d_material());
+    }
+    if scaling.is_changed() || histogram.is_changed() {
+        // the 1d histogram does not depend on the param
+        covers.iter().for_each(|e| commands.entity(e).despawn());
+        spawn_histogram_covering::<A>(
+            &histogram,
+            &scaling,
+            &mut commands,
+            &mut meshes,
+            &mut color_materials,
+        );
+    }
+    // update viz3d mesh, caching the global bins must not rerun what depends on the histogram
+    let (mesh, share) = slice_mesh(
+        p.as_mut(),
+        &img.0,
+        &scaling,
+        histogram.bypass_change_detection(),
+    );
+    viz3d_mesh.single_mut().unwrap().1.0 = meshes.add(mesh);
+    // update param banner
+    banner.single_mut().unwrap().0 = banner_text(p.as_ref(), &scaling, &histogram, share);
+}
+
+pub fn set_viewports(
+    window: Single<&Window>,
+    layout: Res<Layout>,
+    mut query: Query<(&CamViewPort, &mut Camera)>,
+) {
     let size = window.physical_size();
-    let img_filter_width = (size.x as f32 * IMG_VIEW_W_RATIO) as u32;
-    let viz_width = (size.x as f32 * (1. - IMG_VIEW_W_RATIO)) as u32;
-    let viz3d_height = (size.y as f32 * VIZ3D_H_RATIO) as u32;
+    if size.x == 0 || size.y == 0 {
+        // minimized, a zero sized viewport is invalid
+        return;
+    }
 
     for (camera_position, mut camera) in &mut query {
-        let (physical_position, physical_size) = match camera_position {
-            CamViewPort::ImageFilter => (UVec2::new(0, 0), UVec2::new(img_filter_width, size.y)),
-            CamViewPort::Viz2d => (
-                UVec2::new(img_filter_width, viz3d_height),
-                UVec2::new(viz_width, size.y - viz3d_height),
-            ),
-            CamViewPort::Viz3d => (
-                UVec2::new(img_filter_width, 0),
-                UVec2::new(viz_width, viz3d_height),
-            ),
+        let Some(rect) = layout.rect(*camera_position) else {
+            camera.is_active = false;
+            continue;
         };
+        // round both corners so neighbouring panels share an edge
+        let physical_position = (rect.min * size.as_vec2()).as_uvec2();
+        let physical_max = (rect.max * size.as_vec2()).as_uvec2().min(size);
 
+        camera.is_active = true;
         camera.viewport = Some(Viewport {
             physical_position,
-            physical_size,
+            physical_size: (physical_max - physical_position).max(UVec2::ONE),
             ..default()
         });
     }
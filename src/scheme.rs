@@ -0,0 +1,236 @@
+use std::{collections::HashMap, fmt::Display};
+
+use palette::{FromColor, Okhsl, Okhsv, OklabHue, Srgb};
+
+use crate::{Distribution, Space, contrast, distribution::DEFAULT_ACHROMATIC, space::Bin};
+
+/// The 16 ANSI slots, in order, as terminals name them
+pub const ANSI_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright black",
+    "bright red",
+    "bright green",
+    "bright yellow",
+    "bright blue",
+    "bright magenta",
+    "bright cyan",
+    "bright white",
+];
+
+/// the sRGB color each of red, green, yellow, blue, magenta and cyan is named after, its OKHSV
+/// hue is searched around
+const CANONICAL: [[f32; 3]; 6] = [
+    [1., 0., 0.],
+    [0., 1., 0.],
+    [1., 1., 0.],
+    [0., 0., 1.],
+    [1., 0., 1.],
+    [0., 1., 1.],
+];
+/// how far from the canonical hue the image's hues are searched, in degrees
+const HUE_WINDOW: f32 = 25.;
+/// OKHSV saturation times value below which colors are too dull for an accent
+const ACCENT_CHROMA: f32 = 0.2;
+/// saturation and value of a color the image has nothing near
+const FALLBACK_SV: (f32, f32) = (0.75, 0.85);
+/// share of the weight darker than the dark end, and lighter than the light end
+const END_SHARE: f32 = 0.05;
+/// how far from the end lightness the bins giving the end colors their hue are looked at, in
+/// percent
+const END_WINDOW: f32 = 5.;
+/// the background and foreground are at most tinted with the hue of their end
+const END_SATURATION: f32 = 0.2;
+/// a dark background is no lighter than this, a light one no darker than 1 minus this
+const BACKGROUND_LIMIT: f32 = 0.2;
+/// OKHSL lightness between the normal and the bright colors, and between the background and the
+/// black or white next to it
+const BRIGHT_STEP: f32 = 0.1;
+
+/// A terminal color scheme made from the colors of an image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scheme {
+    pub background: Srgb,
+    pub foreground: Srgb,
+    /// in the order of ANSI_NAMES
+    pub ansi: [Srgb; 16],
+}
+
+impl Scheme {
+    /// Make a scheme from colors along with how much each of them counts. The background is the
+    /// dark end of the lightness distribution when most colors are dark, the light end otherwise,
+    /// and the foreground the other end made legible on it. Each of red to cyan is the heaviest
+    /// hue near the canonical one, with the lightness nearest to its own that gives `min_ratio`
+    /// WCAG contrast on the background, and its bright variant is further from the background.
+    /// Black and white are the two ends, the one that is the background made to stand out from it,
+    /// and bright black the gray between them. None without any weight
+    pub fn from_weighted(
+        colors: impl IntoIterator<Item = (Srgb, f32)>,
+        min_ratio: f32,
+    ) -> Option<Self> {
+        let colors: Vec<_> = colors.into_iter().collect();
+        let lightness = Distribution::from_weighted(Space::Okhsl, 0., colors.iter().copied());
+        let hues = Distribution::from_weighted(Space::Okhsv, DEFAULT_ACHROMATIC, colors);
+        if lightness.total() <= 0. {
+            return None;
+        }
+
+        let histogram = lightness.histogram();
+        let dark = percentile(&histogram, END_SHARE);
+        let light = percentile(&histogram, 1. - END_SHARE);
+        let is_dark = percentile(&histogram, 0.5) < 50.;
+        let dark_end = end_color(&lightness, dark);
+        let light_end = end_color(&lightness, light);
+
+        let (background, foreground) = if is_dark {
+            let l = dark_end.lightness.min(BACKGROUND_LIMIT);
+            (at(dark_end, l), light_end)
+        } else {
+            let l = light_end.lightness.max(1. - BACKGROUND_LIMIT);
+            (at(light_end, l), dark_end)
+        };
+        let background = Srgb::from_color(background);
+        let fallback = Okhsl::new(0., 0., if is_dark { 1. } else { 0. });
+        let foreground = contrast::fix_lightness(Srgb::from_color(foreground), background)
+            .map_or(fallback, |l| at(foreground, l));
+        let (dark_end, light_end) = if is_dark {
+            (Okhsl::from_color(background), foreground)
+        } else {
+            (foreground, Okhsl::from_color(background))
+        };
+
+        // away from the background
+        let brighter = if is_dark { BRIGHT_STEP } else { -BRIGHT_STEP };
+        let legible = |color: Okhsl| {
+            let rgb = Srgb::from_color(color);
+            let l = contrast::reach_ratio(rgb, background, min_ratio).unwrap_or(color.lightness);
+            at(color, l)
+        };
+        let mut ansi = [Srgb::new(0., 0., 0.); 16];
+        for (slot, canonical) in CANONICAL.iter().enumerate() {
+            let normal = legible(Okhsl::from_color(accent(&hues, Srgb::from(*canonical))));
+            let bright = at(normal, (normal.lightness + brighter).clamp(0., 1.));
+            ansi[slot + 1] = Srgb::from_color(normal);
+            ansi[slot + 9] = Srgb::from_color(legible(bright));
+        }
+        // black and white stand out from the background, bright white from white
+        let gray = (dark_end.lightness + light_end.lightness) / 2.;
+        let (black, white, bright_white) = if is_dark {
+            let black = (dark_end.lightness + BRIGHT_STEP).min(gray);
+            let bright_white = (light_end.lightness + BRIGHT_STEP).min(1.);
+            (black, light_end.lightness, bright_white)
+        } else {
+            let white = (light_end.lightness - BRIGHT_STEP).max(gray);
+            (dark_end.lightness, white, light_end.lightness)
+        };
+        ansi[0] = Srgb::from_color(at(dark_end, black));
+        ansi[8] = Srgb::from_color(at(dark_end, gray));
+        ansi[7] = Srgb::from_color(at(light_end, white));
+        ansi[15] = Srgb::from_color(at(light_end, bright_white));
+
+        Some(Scheme {
+            background,
+            foreground: Srgb::from_color(foreground),
+            ansi,
+        })
+    }
+
+    /// whether the background is the dark end
+    pub fn is_dark(&self) -> bool {
+        Okhsl::from_color(self.background).lightness < 0.5
+    }
+}
+
+/// Xresources, e.g. `*.color1: #cc6666`
+impl Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = |c: Srgb| format!("#{:x}", c.into_format::<u8>());
+        writeln!(f, "*.background: {}", hex(self.background))?;
+        writeln!(f, "*.foreground: {}", hex(self.foreground))?;
+        for (i, color) in self.ansi.iter().enumerate() {
+            writeln!(f, "*.color{}: {}", i, hex(*color))?;
+        }
+        Ok(())
+    }
+}
+
+/// the color with another OKHSL lightness
+fn at(color: Okhsl, lightness: f32) -> Okhsl {
+    Okhsl::new(color.hue, color.saturation, lightness)
+}
+
+/// the lightness slice, in percent, below which `share` of the weight lies
+fn percentile(histogram: &[(f32, f32)], share: f32) -> f32 {
+    let mut below = 0.;
+    for (slice, s) in histogram {
+        below += s;
+        if below >= share {
+            return *slice;
+        }
+    }
+    histogram.last().map_or(50., |(slice, _)| *slice)
+}
+
+/// the heaviest hue and saturation near a lightness of the OKHSL distribution, at that lightness
+/// and at most END_SATURATION
+fn end_color(lightness: &Distribution, l: f32) -> Okhsl {
+    let b = Space::Okhsl.binning();
+    let heaviest = lightness
+        .bins(true)
+        .into_iter()
+        .filter(|((slice, _), _)| (*slice as f32 - l).abs() <= END_WINDOW)
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    let (hue, saturation) = heaviest.map_or((0., 0.), |((_, (x, z)), _)| {
+        (
+            x as f32 + b.x_delta / 2.,
+            (z as f32 + b.z_delta / 2.) / 100.,
+        )
+    });
+    Okhsl::new(
+        hue,
+        saturation.min(END_SATURATION),
+        (l + b.delta / 2.) / 100.,
+    )
+}
+
+/// the heaviest hue slice of the OKHSV distribution near the hue of `canonical`, with the mean
+/// saturation and value of its colors
+fn accent(hues: &Distribution, canonical: Srgb) -> Okhsv {
+    let b = Space::Okhsv.binning();
+    let target = Okhsv::from_color(canonical).hue;
+    let near = |slice: i64| {
+        let hue = OklabHue::new(slice as f32 + b.delta / 2.);
+        (hue - target).into_degrees().abs() <= HUE_WINDOW
+    };
+    let bins: Vec<(Bin, f64)> = hues
+        .bins(false)
+        .into_iter()
+        .filter(|((slice, (x, z)), _)| near(*slice) && (x * z) as f32 / 10000. >= ACCENT_CHROMA)
+        .collect();
+    let mut slices: HashMap<i64, f64> = HashMap::new();
+    for ((slice, _), weight) in &bins {
+        *slices.entry(*slice).or_insert(0.) += weight;
+    }
+    let Some((slice, total)) = slices.into_iter().max_by(|a, b| a.1.total_cmp(&b.1)) else {
+        let (s, v) = FALLBACK_SV;
+        return Okhsv::new(target, s, v);
+    };
+    // the middle of each bin, weighted
+    let (s, v) =
+        bins.iter()
+            .filter(|((s, _), _)| *s == slice)
+            .fold((0., 0.), |(s, v), ((_, (x, z)), w)| {
+                let share = (w / total) as f32;
+                (
+                    s + (*x as f32 + b.x_delta / 2.) * share,
+                    v + (*z as f32 + b.z_delta / 2.) * share,
+                )
+            });
+    Okhsv::new(slice as f32 + b.delta / 2., s / 100., v / 100.)
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use bevy::prelude::*;
+
+/// Shape drawn with the select action
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SelectTool {
+    /// drag from one corner to the other
+    Rect,
+    /// click every vertex, then close
+    Polygon,
+    /// drag along the outline
+    Lasso,
+}
+
+impl Display for SelectTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SelectTool::Rect => "rect",
+            SelectTool::Polygon => "polygon",
+            SelectTool::Lasso => "lasso",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// State of the selection being drawn, positions are in image pixels with the origin at the top
+/// left
+#[derive(Resource, Default)]
+pub struct Selection {
+    /// None when not selecting, then the image is panned as usual
+    pub tool: Option<SelectTool>,
+    /// vertices of the shape being drawn
+    pub drawing: Vec<Vec2>,
+    /// cursor over the image, if it is
+    pub cursor: Option<Vec2>,
+}
+
+impl Selection {
+    pub fn cycle_tool(&mut self) {
+        self.tool = match self.tool {
+            None => Some(SelectTool::Rect),
+            Some(SelectTool::Rect) => Some(SelectTool::Polygon),
+            Some(SelectTool::Polygon) => Some(SelectTool::Lasso),
+            Some(SelectTool::Lasso) => None,
+        };
+        self.drawing.clear();
+    }
+}
+
+/// The region statistics are restricted to, the whole image if None
+#[derive(Resource, Default)]
+pub struct Roi(pub Option<Region>);
+
+/// A closed polygon in image pixels, rasterized into spans of pixels per row
+pub struct Region {
+    pub outline: Vec<Vec2>,
+    /// first row with spans
+    top: u32,
+    /// [start, end) pixel columns inside the outline, per row
+    spans: Vec<Vec<(u32, u32)>>,
+}
+
+impl Region {
+    /// None if the outline doesn't cover any pixel centers
+    pub fn new(outline: Vec<Vec2>, texels: Vec2) -> Option<Self> {
+        if outline.len() < 3 {
+            return None;
+        }
+        let min_y = outline.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+        let max_y = outline
+            .iter()
+            .map(|p| p.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let top = min_y.max(0.).floor() as u32;
+        let bottom = max_y.min(texels.y).ceil() as u32;
+
+        // even-odd scanline fill at pixel centers
+        let mut spans = vec![];
+        for j in top..bottom {
+            let y = j as f32 + 0.5;
+            let mut crossings: Vec<f32> = outline
+                .iter()
+                .zip(outline.iter().cycle().skip(1))
+                .filter(|(a, b)| (a.y <= y) != (b.y <= y))
+                .map(|(a, b)| a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y))
+                .collect();
+            crossings.sort_by(f32::total_cmp);
+            let row: Vec<(u32, u32)> = crossings
+                .chunks_exact(2)
+                .map(|c| {
+                    let start = (c[0] - 0.5).max(0.).ceil() as u32;
+                    let end = (c[1] - 0.5).min(texels.x).ceil() as u32;
+                    (start, end)
+                })
+                .filter(|(start, end)| start < end)
+                .collect();
+            spans.push(row);
+        }
+
+        if spans.iter().all(|row| row.is_empty()) {
+            return None;
+        }
+        Some(Region {
+            outline,
+            top,
+            spans,
+        })
+    }
+
+    pub fn contains(&self, i: u32, j: u32) -> bool {
+        j.checked_sub(self.top)
+            .and_then(|row| self.spans.get(row as usize))
+            .is_some_and(|row| row.iter().any(|(start, end)| (*start..*end).contains(&i)))
+    }
+}
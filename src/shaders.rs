@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use bevy::{
+    asset::io::{
+        AssetSource, AssetSourceBuilder,
+        memory::{Dir, MemoryAssetReader},
+    },
+    prelude::*,
+};
+
+/// Asset source the materials load their shaders from, e.g. "shaders://okhsv.wgsl"
+pub const SOURCE: &str = "shaders";
+
+macro_rules! shader {
+    ($name:literal) => {
+        ($name, include_str!(concat!("../shaders/", $name)))
+    };
+}
+
+/// every shader, built into the executable
+const SHADERS: &[(&str, &str)] = &[
+    shader!("cvd.wgsl"),
+    shader!("harmony.wgsl"),
+    shader!("hsl.wgsl"),
+    shader!("hsl_2dviz.wgsl"),
+    shader!("hsl_3dviz.wgsl"),
+    shader!("hsl_common.wgsl"),
+    shader!("hsv.wgsl"),
+    shader!("hsv_2dviz.wgsl"),
+    shader!("hsv_3dviz.wgsl"),
+    shader!("hsv_common.wgsl"),
+    shader!("okhsl.wgsl"),
+    shader!("okhsl_2dviz.wgsl"),
+    shader!("okhsl_3dviz.wgsl"),
+    shader!("okhsv.wgsl"),
+    shader!("okhsv_2dviz.wgsl"),
+    shader!("okhsv_3dviz.wgsl"),
+    shader!("oklab_common.wgsl"),
+];
+
+/// Serve the built-in shaders, or the ones in `dir` to edit them without rebuilding. Has to be
+/// registered before the AssetPlugin
+pub fn source(dir: Option<&Path>) -> AssetSourceBuilder {
+    match dir {
+        Some(dir) => AssetSource::build().with_reader(AssetSource::get_default_reader(
+            dir.to_string_lossy().into_owned(),
+        )),
+        None => {
+            let root = Dir::default();
+            for (name, wgsl) in SHADERS {
+                root.insert_asset_text(Path::new(name), wgsl);
+            }
+            AssetSource::build()
+                .with_reader(move || Box::new(MemoryAssetReader { root: root.clone() }))
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use palette::LinSrgb;
+use sefenbu::Deficiency;
+
+/// The color vision deficiency the image is shown with, and whether the statistics see it too
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Simulation {
+    pub cvd: Option<Deficiency>,
+    /// feed the simulated colors to the providers, so the distribution is what a viewer with the
+    /// deficiency sees
+    pub analysis: bool,
+}
+
+impl Simulation {
+    /// which deficiency the filter shaders simulate, 0 for none, see shaders/cvd.wgsl
+    pub fn shader_index(&self) -> u32 {
+        self.cvd.map_or(0, |cvd| {
+            Deficiency::ALL.iter().position(|d| *d == cvd).unwrap() as u32 + 1
+        })
+    }
+
+    /// the deficiency the statistics are computed with, if any
+    pub fn analyzed(&self) -> Option<Deficiency> {
+        self.cvd.filter(|_| self.analysis)
+    }
+
+    /// The pixels as analyzed, as seen with the deficiency when it is analyzed too
+    pub fn apply(&self, mut image: Image) -> Image {
+        let Some(cvd) = self.analyzed() else {
+            return image;
+        };
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                let Ok(c) = image.get_color_at(i, j) else {
+                    continue;
+                };
+                let c = c.to_linear();
+                let rgb = cvd.simulate(LinSrgb::new(c.red, c.green, c.blue));
+                let seen = Color::linear_rgba(rgb.red, rgb.green, rgb.blue, c.alpha);
+                // every format get_color_at reads can be written
+                let _ = image.set_color_at(i, j, seen);
+            }
+        }
+        image
+    }
+
+    /// which deficiency is simulated, shown over the image, empty for normal vision
+    pub fn note(&self) -> String {
+        match (self.cvd, self.analysis) {
+            (None, _) => String::new(),
+            (Some(cvd), false) => format!("simulating {}", cvd),
+            (Some(cvd), true) => format!("simulating {}, also in the statistics", cvd),
+        }
+    }
+}
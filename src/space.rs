@@ -0,0 +1,166 @@
+use std::{fmt::Display, str::FromStr};
+
+use palette::{FromColor, Hsl, Hsv, Okhsl, Okhsv, Srgb};
+
+use crate::Error;
+
+/// Color spaces a distribution can be looked at in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Space {
+    /// sliced by hue
+    Okhsv,
+    /// sliced by lightness
+    Okhsl,
+    /// sliced by hue
+    Hsv,
+    /// sliced by lightness
+    Hsl,
+}
+
+/// slice and data point of a color, see Space::bin
+pub type Bin = (i64, (i64, i64));
+
+/// How a space is cut into bins: the param it is sliced by, and the two axes of a slice
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Binning {
+    pub min: f32,
+    pub max: f32,
+    /// width of a slice
+    pub delta: f32,
+    pub x_max: f32,
+    pub x_delta: f32,
+    pub z_max: f32,
+    pub z_delta: f32,
+}
+
+const BY_HUE: Binning = Binning {
+    min: 0.,
+    max: 360.,
+    delta: 2.,
+    // X saturation, Z value
+    x_max: 100.,
+    x_delta: 2.,
+    z_max: 100.,
+    z_delta: 2.,
+};
+
+const BY_LIGHTNESS: Binning = Binning {
+    min: 0.,
+    max: 100.,
+    delta: 1.,
+    // X hue, Z saturation
+    x_max: 360.,
+    x_delta: 7.2,
+    z_max: 100.,
+    z_delta: 2.,
+};
+
+/// A color in a space: the param it is sliced by, then the two axes of a slice. Hues are in
+/// degrees, everything else in percent
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Coords {
+    pub param: f32,
+    pub x: f32,
+    pub z: f32,
+}
+
+impl Space {
+    pub const ALL: [Space; 4] = [Space::Okhsv, Space::Okhsl, Space::Hsv, Space::Hsl];
+
+    /// lowercase name, as taken by FromStr
+    pub fn name(self) -> &'static str {
+        match self {
+            Space::Okhsv => "okhsv",
+            Space::Okhsl => "okhsl",
+            Space::Hsv => "hsv",
+            Space::Hsl => "hsl",
+        }
+    }
+
+    pub const fn binning(self) -> Binning {
+        match self {
+            Space::Okhsv | Space::Hsv => BY_HUE,
+            Space::Okhsl | Space::Hsl => BY_LIGHTNESS,
+        }
+    }
+
+    pub fn coords(self, rgb: Srgb) -> Coords {
+        match self {
+            Space::Okhsv => {
+                let c = Okhsv::from_color(rgb);
+                Coords {
+                    param: c.hue.into_positive_degrees(),
+                    x: c.saturation * 100.,
+                    z: c.value * 100.,
+                }
+            }
+            Space::Okhsl => {
+                let c = Okhsl::from_color(rgb);
+                Coords {
+                    param: c.lightness * 100.,
+                    x: c.hue.into_positive_degrees(),
+                    z: c.saturation * 100.,
+                }
+            }
+            Space::Hsv => {
+                let c = Hsv::from_color(rgb);
+                Coords {
+                    param: c.hue.into_positive_degrees(),
+                    x: c.saturation * 100.,
+                    z: c.value * 100.,
+                }
+            }
+            Space::Hsl => {
+                let c = Hsl::from_color(rgb);
+                Coords {
+                    param: c.lightness * 100.,
+                    x: c.hue.into_positive_degrees(),
+                    z: c.saturation * 100.,
+                }
+            }
+        }
+    }
+
+    /// whether the space is sliced by hue, the others are sliced by lightness
+    pub fn by_hue(self) -> bool {
+        matches!(self, Space::Okhsv | Space::Hsv)
+    }
+
+    /// whether the color is too gray for its hue to mean anything, threshold being saturation
+    /// times value in [0,1]. Only spaces sliced by hue have achromatic colors
+    pub fn is_achromatic(self, coords: Coords, threshold: f32) -> bool {
+        match self {
+            Space::Okhsv | Space::Hsv => coords.x * coords.z / 10000. < threshold,
+            Space::Okhsl | Space::Hsl => false,
+        }
+    }
+
+    /// the slice and data point the color falls in, each rounded down to a multiple of its delta
+    pub fn bin(self, coords: Coords) -> Bin {
+        let b = self.binning();
+        (
+            (coords.param / b.delta) as i64 * (b.delta as i64),
+            (
+                (coords.x / b.x_delta) as i64 * (b.x_delta as i64),
+                (coords.z / b.z_delta) as i64 * (b.z_delta as i64),
+            ),
+        )
+    }
+}
+
+impl Display for Space {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Space {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Space::ALL
+            .into_iter()
+            .find(|space| space.name() == s)
+            .ok_or_else(|| Error::UnknownSpace(s.to_string()))
+    }
+}
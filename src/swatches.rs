@@ -0,0 +1,19 @@
+use bevy::prelude::*;
+use palette::Srgb;
+
+/// Most colors picked at once, the contrast matrix grows with the square
+pub const MAX_SWATCHES: usize = 6;
+
+/// Colors picked from the image, compared with each other in the contrast panel
+#[derive(Resource, Default)]
+pub struct Swatches(pub Vec<Srgb>);
+
+impl Swatches {
+    /// add a color, forgetting the oldest one when there are too many
+    pub fn add(&mut self, color: Srgb) {
+        if self.0.len() == MAX_SWATCHES {
+            self.0.remove(0);
+        }
+        self.0.push(color);
+    }
+}
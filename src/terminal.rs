@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use sefenbu::Scheme;
+
+/// The terminal color scheme made from the pixels as analyzed, previewed over the image
+#[derive(Resource, Default)]
+pub struct TerminalScheme {
+    pub shown: bool,
+    /// made again by make_scheme whenever the sample changes while shown
+    pub scheme: Option<Scheme>,
+    /// on X11 and Wayland the copied scheme is served for as long as this lives
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl TerminalScheme {
+    /// put the scheme on the clipboard as Xresources
+    pub fn copy(&mut self) -> Result<(), String> {
+        let err = |e: &dyn std::fmt::Display| format!("Cannot copy the terminal scheme: {}", e);
+        let Some(scheme) = self.scheme else {
+            return Err(err(&"it is made once shown"));
+        };
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self
+                .clipboard
+                .insert(arboard::Clipboard::new().map_err(|e| err(&e))?),
+        };
+        clipboard.set_text(scheme.to_string()).map_err(|e| err(&e))
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fmt::Display, str::FromStr};
+
+use palette::LinSrgb;
+
+use crate::Error;
+
+/// How linear colors brighter than white are brought into [0,1] before they are analyzed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ToneMap {
+    /// leave colors alone and clip whatever is above 1
+    #[default]
+    Clip,
+    /// compress by luminance, which keeps hues, very saturated highlights still clip
+    Reinhard,
+    /// the ACES filmic curve fit by Krzysztof Narkowicz, per channel
+    Aces,
+}
+
+/// share of each channel in the luminance of a linear sRGB color
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+impl ToneMap {
+    pub const ALL: [ToneMap; 3] = [ToneMap::Clip, ToneMap::Reinhard, ToneMap::Aces];
+
+    /// lowercase name, as taken by FromStr
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMap::Clip => "clip",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Aces => "aces",
+        }
+    }
+
+    /// the one after this in ALL, wrapping around
+    pub fn next(self) -> ToneMap {
+        let i = ToneMap::ALL.iter().position(|t| *t == self).unwrap();
+        ToneMap::ALL[(i + 1) % ToneMap::ALL.len()]
+    }
+
+    /// the color brightened by `exposure` stops, then mapped into [0,1]. Negative channels, which
+    /// some renderers write, are taken as 0
+    pub fn apply(self, rgb: LinSrgb, exposure: f32) -> LinSrgb {
+        let gain = exposure.exp2();
+        let [r, g, b] = [rgb.red, rgb.green, rgb.blue].map(|c| (c * gain).max(0.));
+        let [r, g, b] = match self {
+            ToneMap::Clip => [r, g, b],
+            ToneMap::Reinhard => {
+                let luminance = LUMINANCE[0] * r + LUMINANCE[1] * g + LUMINANCE[2] * b;
+                [r, g, b].map(|c| c / (1. + luminance))
+            }
+            ToneMap::Aces => {
+                [r, g, b].map(|c| c * (2.51 * c + 0.03) / (c * (2.43 * c + 0.59) + 0.14))
+            }
+        };
+        LinSrgb::new(r.min(1.), g.min(1.), b.min(1.))
+    }
+}
+
+impl Display for ToneMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ToneMap::ALL
+            .into_iter()
+            .find(|tonemap| tonemap.name() == s)
+            .ok_or_else(|| Error::UnknownToneMap(s.to_string()))
+    }
+}
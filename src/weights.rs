@@ -0,0 +1,271 @@
+use std::fmt::Display;
+
+use bevy::prelude::*;
+use clap::ValueEnum;
+
+use crate::selection::Region;
+
+/// How the alpha channel affects the statistics
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AlphaHandling {
+    // leave out fully transparent pixels
+    #[default]
+    Exclude,
+    // count every pixel by its opacity
+    Weight,
+}
+
+impl Display for AlphaHandling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AlphaHandling::Exclude => "exclude",
+            AlphaHandling::Weight => "weight",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// How pixels are picked when analyzing fewer than all of them
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Sampling {
+    // every n-th pixel of every n-th row, like a nearest neighbour downscale
+    Downscale,
+    // pixels picked at random
+    Random,
+    // one random pixel from each cell of a grid
+    #[default]
+    Stratified,
+}
+
+impl Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Sampling::Downscale => "downscale",
+            Sampling::Random => "random",
+            Sampling::Stratified => "stratified",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The analyzed pixels and how much each of them counts
+pub struct PixelWeights {
+    /// x, y and weight, pixels with weight 0 are left out
+    pixels: Vec<(u32, u32, f32)>,
+    total: f64,
+    /// pixels picked by the sampler, including those left out
+    sampled: usize,
+    /// pixels in the image
+    size: usize,
+}
+
+impl PixelWeights {
+    fn new(
+        img: &Image,
+        picked: impl IntoIterator<Item = (u32, u32)>,
+        weight_of: impl Fn(u32, u32) -> f32,
+    ) -> Self {
+        let mut pixels = vec![];
+        let mut sampled = 0;
+        for (i, j) in picked {
+            sampled += 1;
+            let weight = weight_of(i, j);
+            if weight > 0. {
+                pixels.push((i, j, weight));
+            }
+        }
+        let total = pixels.iter().map(|(_, _, w)| *w as f64).sum();
+        PixelWeights {
+            pixels,
+            total,
+            sampled,
+            size: img.width() as usize * img.height() as usize,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32, f32)> + '_ {
+        self.pixels.iter().copied()
+    }
+
+    /// sum of all weights, never 0 so ratios stay finite
+    pub fn total(&self) -> f64 {
+        self.total.max(f64::MIN_POSITIVE)
+    }
+
+    /// share of the image's pixels that were sampled
+    pub fn sampled_ratio(&self) -> f32 {
+        self.sampled as f32 / self.size.max(1) as f32
+    }
+}
+
+/// Per pixel weights from --mask, white for pixels analyzed fully and black for pixels left out
+pub struct Mask {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl Mask {
+    /// Err when bevy can't read the mask back
+    pub fn new(mask: &Image, invert: bool) -> Result<Self, String> {
+        let (w, h) = (mask.width(), mask.height());
+        let mut values = Vec::with_capacity(w as usize * h as usize);
+        for j in 0..h {
+            for i in 0..w {
+                let c = mask
+                    .get_color_at(i, j)
+                    .map_err(|e| format!("Cannot read mask: {}", e))?;
+                let c: Srgba = c.into();
+                // transparent parts of the mask count as black
+                let v = (c.red + c.green + c.blue) / 3. * c.alpha;
+                values.push(if invert { 1. - v } else { v });
+            }
+        }
+        Ok(Mask {
+            width: w,
+            height: h,
+            values,
+        })
+    }
+
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.width, self.height)
+    }
+
+    fn get(&self, i: u32, j: u32) -> f32 {
+        self.values[j as usize * self.width as usize + i as usize]
+    }
+}
+
+/// smallest sample worth a refinement step of its own
+const MIN_LEVEL_PIXELS: usize = 1 << 16;
+
+/// Picks the analyzed pixels, from a coarse estimate up to the final sample
+#[derive(Resource)]
+pub struct Sampler {
+    pub sampling: Sampling,
+    pub seed: u64,
+    pub alpha: AlphaHandling,
+    pub mask: Option<Mask>,
+    /// share of all pixels analyzed at each level, ascending
+    fractions: Vec<f64>,
+}
+
+impl Sampler {
+    pub fn new(
+        img: &Image,
+        sampling: Sampling,
+        max_pixels: Option<usize>,
+        seed: u64,
+        alpha: AlphaHandling,
+        mask: Option<Mask>,
+    ) -> Self {
+        let size = (img.width() as usize * img.height() as usize).max(1);
+        let target = max_pixels.map_or(size, |m| m.min(size)).max(1);
+        // each level has 4 times the pixels of the one before
+        let mut counts = vec![target];
+        while counts[counts.len() - 1] / 4 >= MIN_LEVEL_PIXELS {
+            counts.push(counts[counts.len() - 1] / 4);
+        }
+        Sampler {
+            sampling,
+            seed,
+            alpha,
+            mask,
+            fractions: counts
+                .iter()
+                .rev()
+                .map(|c| *c as f64 / size as f64)
+                .collect(),
+        }
+    }
+
+    pub fn levels(&self) -> usize {
+        self.fractions.len()
+    }
+
+    /// pixels of a refinement level, 0 being the coarsest, inside the region if any
+    pub fn weights(&self, img: &Image, level: usize, region: Option<&Region>) -> PixelWeights {
+        let (w, h) = (img.width(), img.height());
+        let weight_of = |i, j| {
+            if region.is_some_and(|r: &Region| !r.contains(i, j)) {
+                return 0.;
+            }
+            // pixels bevy can't read back are left out
+            let Ok(a) = img.get_color_at(i, j).map(|c| c.alpha()) else {
+                return 0.;
+            };
+            let weight = match self.alpha {
+                AlphaHandling::Exclude if a > 0. => 1.,
+                AlphaHandling::Exclude => 0.,
+                AlphaHandling::Weight => a,
+            };
+            weight * self.mask.as_ref().map_or(1., |m| m.get(i, j))
+        };
+
+        let fraction = self.fractions[level];
+        if fraction >= 1. {
+            let all = (0..w).flat_map(|i| (0..h).map(move |j| (i, j)));
+            return PixelWeights::new(img, all, weight_of);
+        }
+
+        // side of the square of pixels each sample stands for
+        let cell = (1. / fraction).sqrt().ceil() as u32;
+        let seed = self.seed;
+        match self.sampling {
+            Sampling::Downscale => {
+                let picked = (cell / 2..w)
+                    .step_by(cell as usize)
+                    .flat_map(|i| (cell / 2..h).step_by(cell as usize).map(move |j| (i, j)));
+                PixelWeights::new(img, picked, weight_of)
+            }
+            Sampling::Random => {
+                // the start of the same shuffled order at every level, so finer samples contain
+                // the coarser ones
+                let size = w as u64 * h as u64;
+                let bits = size.next_power_of_two().trailing_zeros();
+                let count = (fraction * size as f64).round() as usize;
+                let picked = (0..1 << bits)
+                    .map(|index| permute(seed, bits, index))
+                    .filter(|index| *index < size)
+                    .take(count)
+                    .map(|index| ((index % w as u64) as u32, (index / w as u64) as u32));
+                PixelWeights::new(img, picked, weight_of)
+            }
+            Sampling::Stratified => {
+                let (cols, rows) = (w.div_ceil(cell), h.div_ceil(cell));
+                let picked = (0..cols).flat_map(|ci| {
+                    (0..rows).map(move |cj| {
+                        let r = hash(seed, cj as u64 * cols as u64 + ci as u64);
+                        let i = (ci * cell + (r % cell as u64) as u32).min(w - 1);
+                        let j = (cj * cell + ((r >> 32) % cell as u64) as u32).min(h - 1);
+                        (i, j)
+                    })
+                });
+                PixelWeights::new(img, picked, weight_of)
+            }
+        }
+    }
+}
+
+/// Shuffle of the indices below 2^bits, every step a bijection so each index comes out once.
+/// Walking it skips the ones past the last pixel, which are less than half
+fn permute(seed: u64, bits: u32, index: u64) -> u64 {
+    let mask = (1 << bits) - 1;
+    let mut x = index;
+    for round in 0..3 {
+        x ^= hash(seed, round) & mask;
+        x = x.wrapping_mul(0x9e37_79b9_7f4a_7c15) & mask;
+        x ^= x >> (bits / 2).max(1);
+    }
+    x
+}
+
+/// splitmix64 of seed and index, stateless so any pixel can be looked up on its own
+fn hash(seed: u64, index: u64) -> u64 {
+    let mut x = seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}